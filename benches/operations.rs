@@ -0,0 +1,407 @@
+//! Compares VEBTree against the standard library sets and a plain
+//!     bitset, for each operation over a range of universes and
+//!     densities.
+//!
+//! Run with `cargo bench`.  To compare two releases, save a baseline
+//!     on the first and diff the second against it:
+//!
+//! ```text
+//! cargo bench --bench operations -- --save-baseline v0.1.4
+//! cargo bench --bench operations -- --baseline v0.1.4
+//! ```
+//!
+//! Criterion keeps the raw results and an HTML report of every
+//!     comparison under target/criterion.
+
+#[macro_use]
+extern crate criterion;
+extern crate vebtrees;
+
+use criterion::{BatchSize, BenchmarkGroup, BenchmarkId, Criterion, Throughput};
+use criterion::measurement::WallTime;
+use std::collections::{BTreeSet, HashSet};
+use std::time::Duration;
+use vebtrees::VEBTree;
+
+/// The universes benchmarked, from one that fits in a few leaves to
+///     one where a dense tree spans several megabytes
+const UNIVERSES: [usize; 3] = [1 << 10, 1 << 16, 1 << 22];
+
+/// The fraction of each universe that is filled, in percent
+const DENSITIES: [usize; 3] = [1, 10, 50];
+
+/// The number of successor and predecessor queries timed per
+///     iteration
+const QUERIES: usize = 1000;
+
+/// The operations every structure under test provides.  HashSet has
+///     no order, so its successor and predecessor are None and those
+///     benchmarks skip it.
+trait Set: Clone {
+    const NAME: &'static str;
+    const ORDERED: bool = true;
+
+    fn with_universe(universe: usize) -> Self;
+    fn insert(&mut self, key: u32);
+    fn remove(&mut self, key: u32);
+    fn succ(&self, key: u32) -> Option<u32>;
+    fn pred(&self, key: u32) -> Option<u32>;
+    fn sum(&self) -> u64;
+}
+
+impl Set for VEBTree<u32> {
+    const NAME: &'static str = "VEBTree";
+
+    fn with_universe(universe: usize) -> Self {
+        VEBTree::new(universe)
+    }
+
+    fn insert(&mut self, key: u32) {
+        VEBTree::insert(self, key);
+    }
+
+    fn remove(&mut self, key: u32) {
+        self.delete(key);
+    }
+
+    fn succ(&self, key: u32) -> Option<u32> {
+        self.findnext(key)
+    }
+
+    fn pred(&self, key: u32) -> Option<u32> {
+        self.findprev(key)
+    }
+
+    fn sum(&self) -> u64 {
+        self.iter().map(u64::from).sum()
+    }
+}
+
+/// A VEBTree created with new_sparse
+#[derive(Clone)]
+struct SparseVEBTree(VEBTree<u32>);
+
+impl Set for SparseVEBTree {
+    const NAME: &'static str = "VEBTree (sparse)";
+
+    fn with_universe(universe: usize) -> Self {
+        SparseVEBTree(VEBTree::new_sparse(universe))
+    }
+
+    fn insert(&mut self, key: u32) {
+        self.0.insert(key);
+    }
+
+    fn remove(&mut self, key: u32) {
+        self.0.delete(key);
+    }
+
+    fn succ(&self, key: u32) -> Option<u32> {
+        self.0.findnext(key)
+    }
+
+    fn pred(&self, key: u32) -> Option<u32> {
+        self.0.findprev(key)
+    }
+
+    fn sum(&self) -> u64 {
+        self.0.iter().map(u64::from).sum()
+    }
+}
+
+impl Set for BTreeSet<u32> {
+    const NAME: &'static str = "BTreeSet";
+
+    fn with_universe(_: usize) -> Self {
+        BTreeSet::new()
+    }
+
+    fn insert(&mut self, key: u32) {
+        BTreeSet::insert(self, key);
+    }
+
+    fn remove(&mut self, key: u32) {
+        BTreeSet::remove(self, &key);
+    }
+
+    fn succ(&self, key: u32) -> Option<u32> {
+        self.range(key + 1..).next().cloned()
+    }
+
+    fn pred(&self, key: u32) -> Option<u32> {
+        self.range(..key).next_back().cloned()
+    }
+
+    fn sum(&self) -> u64 {
+        self.iter().map(|&key| u64::from(key)).sum()
+    }
+}
+
+impl Set for HashSet<u32> {
+    const NAME: &'static str = "HashSet";
+    const ORDERED: bool = false;
+
+    fn with_universe(_: usize) -> Self {
+        HashSet::new()
+    }
+
+    fn insert(&mut self, key: u32) {
+        HashSet::insert(self, key);
+    }
+
+    fn remove(&mut self, key: u32) {
+        HashSet::remove(self, &key);
+    }
+
+    fn succ(&self, _: u32) -> Option<u32> {
+        None
+    }
+
+    fn pred(&self, _: u32) -> Option<u32> {
+        None
+    }
+
+    fn sum(&self) -> u64 {
+        self.iter().map(|&key| u64::from(key)).sum()
+    }
+}
+
+/// A flat bitmap over the universe, one bit per key, scanning a word
+///     at a time for successors and predecessors
+#[derive(Clone)]
+struct Bitset {
+    words: Vec<u64>,
+}
+
+impl Set for Bitset {
+    const NAME: &'static str = "bitset";
+
+    fn with_universe(universe: usize) -> Self {
+        Bitset {
+            words: vec![0; universe.div_ceil(64)],
+        }
+    }
+
+    fn insert(&mut self, key: u32) {
+        self.words[key as usize / 64] |= 1 << (key % 64);
+    }
+
+    fn remove(&mut self, key: u32) {
+        self.words[key as usize / 64] &= !(1 << (key % 64));
+    }
+
+    fn succ(&self, key: u32) -> Option<u32> {
+        let key = key as usize + 1;
+        let mut word = key / 64;
+        let mut bits = *self.words.get(word)? & (!0 << (key % 64));
+        while bits == 0 {
+            word += 1;
+            bits = *self.words.get(word)?;
+        }
+        Some((word * 64) as u32 + bits.trailing_zeros())
+    }
+
+    fn pred(&self, key: u32) -> Option<u32> {
+        let mut word = key as usize / 64;
+        let mut bits = self.words[word] & ((1 << (key % 64)) - 1);
+        while bits == 0 {
+            word = word.checked_sub(1)?;
+            bits = self.words[word];
+        }
+        Some((word * 64) as u32 + 63 - bits.leading_zeros())
+    }
+
+    fn sum(&self) -> u64 {
+        let mut total = 0;
+        for (index, &word) in self.words.iter().enumerate() {
+            let mut bits = word;
+            while bits != 0 {
+                total += (index * 64) as u64 + u64::from(bits.trailing_zeros());
+                bits &= bits - 1;
+            }
+        }
+        total
+    }
+}
+
+/// A fixed pseudo-random stream, so that every run and every
+///     structure sees the same keys
+struct Lcg(u64);
+
+impl Lcg {
+    fn below(&mut self, bound: usize) -> u32 {
+        self.0 = self.0.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        ((self.0 >> 33) % bound as u64) as u32
+    }
+}
+
+/// The inputs shared by every structure for one universe and density
+///
+/// # Fields
+/// * universe: usize - the number of keys the structures can hold
+/// * keys: `Vec<u32>` - distinct keys filling the requested share of
+///   the universe, in random order
+/// * queries: `Vec<u32>` - random points of the universe to look up
+///   neighbours of, which may or may not be keys
+struct Workload {
+    universe: usize,
+    keys: Vec<u32>,
+    queries: Vec<u32>,
+}
+
+impl Workload {
+    fn new(universe: usize, density: usize) -> Self {
+        let mut rng = Lcg(universe as u64 ^ density as u64);
+        let wanted = universe * density / 100;
+        let mut seen = vec![false; universe];
+        let mut keys = Vec::with_capacity(wanted);
+        while keys.len() < wanted {
+            let key = rng.below(universe);
+            if !seen[key as usize] {
+                seen[key as usize] = true;
+                keys.push(key);
+            }
+        }
+        let queries = (0..QUERIES).map(|_| rng.below(universe)).collect();
+        Workload {
+            universe,
+            keys,
+            queries,
+        }
+    }
+
+    fn filled<S: Set>(&self) -> S {
+        let mut set = S::with_universe(self.universe);
+        for &key in &self.keys {
+            set.insert(key);
+        }
+        set
+    }
+
+    fn label(&self, density: usize) -> String {
+        format!("{}/{}%", self.universe, density)
+    }
+}
+
+fn bench_insert<S: Set>(group: &mut BenchmarkGroup<WallTime>, work: &Workload, density: usize) {
+    group.throughput(Throughput::Elements(work.keys.len() as u64));
+    group.bench_with_input(BenchmarkId::new(S::NAME, work.label(density)), work, |b, work| {
+        b.iter_batched_ref(
+            || S::with_universe(work.universe),
+            |set| {
+                for &key in &work.keys {
+                    set.insert(key);
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_delete<S: Set>(group: &mut BenchmarkGroup<WallTime>, work: &Workload, density: usize) {
+    let full: S = work.filled();
+    group.throughput(Throughput::Elements(work.keys.len() as u64));
+    group.bench_with_input(BenchmarkId::new(S::NAME, work.label(density)), work, |b, work| {
+        b.iter_batched_ref(
+            || full.clone(),
+            |set| {
+                for &key in &work.keys {
+                    set.remove(key);
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_succ<S: Set>(group: &mut BenchmarkGroup<WallTime>, work: &Workload, density: usize) {
+    if !S::ORDERED {
+        return;
+    }
+    let full: S = work.filled();
+    group.throughput(Throughput::Elements(work.queries.len() as u64));
+    group.bench_with_input(BenchmarkId::new(S::NAME, work.label(density)), work, |b, work| {
+        b.iter(|| work.queries.iter().filter_map(|&key| full.succ(key)).count())
+    });
+}
+
+fn bench_pred<S: Set>(group: &mut BenchmarkGroup<WallTime>, work: &Workload, density: usize) {
+    if !S::ORDERED {
+        return;
+    }
+    let full: S = work.filled();
+    group.throughput(Throughput::Elements(work.queries.len() as u64));
+    group.bench_with_input(BenchmarkId::new(S::NAME, work.label(density)), work, |b, work| {
+        b.iter(|| work.queries.iter().filter_map(|&key| full.pred(key)).count())
+    });
+}
+
+fn bench_iter<S: Set>(group: &mut BenchmarkGroup<WallTime>, work: &Workload, density: usize) {
+    let full: S = work.filled();
+    group.throughput(Throughput::Elements(work.keys.len() as u64));
+    group.bench_with_input(BenchmarkId::new(S::NAME, work.label(density)), work, |b, _| {
+        b.iter(|| full.sum())
+    });
+}
+
+/// Checks that the ordered structures agree on every query before
+///     any of them are timed, so a broken bitset cannot make the tree
+///     look slow or fast
+fn check_agreement(work: &Workload) {
+    let reference: BTreeSet<u32> = work.filled();
+    let dense: VEBTree<u32> = work.filled();
+    let sparse: SparseVEBTree = work.filled();
+    let bitset: Bitset = work.filled();
+    for &key in &work.queries {
+        let expected = (reference.succ(key), reference.pred(key));
+        assert_eq!((dense.succ(key), dense.pred(key)), expected);
+        assert_eq!((sparse.succ(key), sparse.pred(key)), expected);
+        assert_eq!((bitset.succ(key), bitset.pred(key)), expected);
+    }
+    assert_eq!(bitset.sum(), reference.sum());
+}
+
+/// Runs one operation for every structure, universe and density,
+///     as a single criterion group so their reports line up
+macro_rules! compare {
+    ($c:expr, $name:expr, $bench:ident, $workloads:expr) => {{
+        let mut group = $c.benchmark_group($name);
+        for &(density, ref work) in $workloads {
+            $bench::<VEBTree<u32>>(&mut group, work, density);
+            $bench::<SparseVEBTree>(&mut group, work, density);
+            $bench::<BTreeSet<u32>>(&mut group, work, density);
+            $bench::<HashSet<u32>>(&mut group, work, density);
+            $bench::<Bitset>(&mut group, work, density);
+        }
+        group.finish();
+    }};
+}
+
+fn operations(c: &mut Criterion) {
+    let workloads: Vec<(usize, Workload)> = UNIVERSES
+        .iter()
+        .flat_map(|&universe| {
+            DENSITIES
+                .iter()
+                .map(move |&density| (density, Workload::new(universe, density)))
+        })
+        .collect();
+    for (_, work) in &workloads {
+        check_agreement(work);
+    }
+    compare!(c, "insert", bench_insert, &workloads);
+    compare!(c, "delete", bench_delete, &workloads);
+    compare!(c, "succ", bench_succ, &workloads);
+    compare!(c, "pred", bench_pred, &workloads);
+    compare!(c, "iter", bench_iter, &workloads);
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default()
+        .sample_size(20)
+        .warm_up_time(Duration::from_millis(500))
+        .measurement_time(Duration::from_secs(2));
+    targets = operations
+}
+criterion_main!(benches);
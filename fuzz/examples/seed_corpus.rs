@@ -0,0 +1,233 @@
+//! Writes the seed corpus of every fuzz target from the scenarios of
+//! the unit tests in src/tests.rs.
+//!
+//! Run from the fuzz directory with `cargo run --example seed_corpus`.
+//! Each scenario becomes a script for the ops target, and the tree it
+//! leaves behind becomes a snapshot for the snapshot target and a JSON
+//! document for the deserialize target.  Every scenario is run (and so
+//! checked against BTreeSet) before anything is written.
+
+use std::fs;
+use std::path::Path;
+use vebtrees_fuzz::{Op, Script};
+
+fn scenarios() -> Vec<(&'static str, Script)> {
+    let mut scenarios = Vec::new();
+
+    let mut script = Script::new(16, false);
+    for key in 0..10 {
+        script.push(Op::Insert, key);
+    }
+    for key in 0..16 {
+        script.push(Op::Contains, key);
+    }
+    scenarios.push(("contains_correctness", script));
+
+    let mut script = Script::new(16, true);
+    for key in 0..8 {
+        script.push(Op::Insert, key);
+    }
+    for key in 0..16 {
+        script.push(Op::Search, key);
+    }
+    scenarios.push(("search_correctness", script));
+
+    let mut script = Script::new(16, false);
+    for key in 0..16 {
+        script.push(Op::Insert, key);
+    }
+    for key in 0..16 {
+        script.push(Op::Delete, key);
+    }
+    scenarios.push(("insert_and_delete_correctness", script));
+
+    for &(name, universe, sparse) in &[
+        ("insert_and_delete_correctness_odd_dense", 23, false),
+        ("insert_and_delete_correctness_odd_sparse", 29, true),
+    ] {
+        let mut script = Script::new(universe, sparse);
+        for key in 0..universe as u32 {
+            script.push(Op::Insert, key);
+        }
+        for key in 0..universe as u32 {
+            script.push(Op::Delete, key);
+        }
+        scenarios.push((name, script));
+    }
+
+    let mut script = Script::new(16, false);
+    for key in (0..16).rev() {
+        script.push(Op::Insert, key).push(Op::Minimum, 0);
+    }
+    for key in 0..15 {
+        script.push(Op::Delete, key).push(Op::Minimum, 0);
+    }
+    scenarios.push(("minimum_correctness", script));
+
+    let mut script = Script::new(16, true);
+    for key in 0..16 {
+        script.push(Op::Insert, key).push(Op::Maximum, 0);
+    }
+    for key in (0..16).rev() {
+        script.push(Op::Maximum, 0).push(Op::Delete, key);
+    }
+    scenarios.push(("maximum_correctness", script));
+
+    for &(name, sparse, op) in &[
+        ("findnext_correctness", false, Op::FindNext),
+        ("findprev_correctness", true, Op::FindPrev),
+    ] {
+        let mut script = Script::new(16, sparse);
+        for key in (0..16).filter(|key| key % 3 == 0) {
+            script.push(Op::Insert, key);
+        }
+        for key in 0..16 {
+            script.push(op, key);
+        }
+        scenarios.push((name, script));
+    }
+
+    // Every key present, so the snapshot is written as a bitmap
+    let mut script = Script::new(256, false);
+    for key in 0..256 {
+        script.push(Op::Insert, key);
+    }
+    script.push(Op::FindNext, 255).push(Op::FindPrev, 0);
+    scenarios.push(("full_width_u8_universe", script));
+
+    let mut script = Script::new(1 << 32, true);
+    for &key in &[0, 12_345, 1 << 31, u32::MAX] {
+        script.push(Op::Insert, key);
+    }
+    for &key in &[1, 12_345, (1 << 31) - 1, u32::MAX - 1, u32::MAX] {
+        script
+            .push(Op::FindNext, key)
+            .push(Op::FindPrev, key)
+            .push(Op::Ceil, key)
+            .push(Op::Floor, key);
+    }
+    script.push(Op::Delete, 1 << 31).push(Op::FindNext, 12_345);
+    scenarios.push(("sparse_huge_universe", script));
+
+    let mut script = Script::new(100, false);
+    script
+        .push(Op::Insert, 5)
+        .push(Op::Insert, 99)
+        .push(Op::TryInsert, 100)
+        .push(Op::TryInsert, 1000)
+        .push(Op::TryInsert, 50)
+        .push(Op::Contains, 150)
+        .push(Op::TryContains, 100)
+        .push(Op::Search, 100)
+        .push(Op::FindNext, 200)
+        .push(Op::FindPrev, 200)
+        .push(Op::Ceil, 100)
+        .push(Op::Floor, 100)
+        .push(Op::Delete, 100)
+        .push(Op::TryDelete, 5000)
+        .push(Op::TryDelete, 5)
+        .push(Op::Rank, 1000);
+    scenarios.push(("out_of_range_keys", script));
+
+    let mut script = Script::new(200, false);
+    let boundaries = [0, 63, 64, 127, 128, 191, 199];
+    for &key in &boundaries {
+        script.push(Op::Insert, key);
+    }
+    for &key in &boundaries {
+        script.push(Op::FindNext, key).push(Op::FindPrev, key);
+    }
+    script.push(Op::Delete, 64).push(Op::FindNext, 63).push(Op::FindPrev, 127);
+    scenarios.push(("bitmap_leaf_boundaries", script));
+
+    let mut script = Script::new(1000, true);
+    for &key in &[500, 3, 999, 42, 700] {
+        script.push(Op::Insert, key);
+    }
+    for _ in 0..3 {
+        script.push(Op::PopMin, 0).push(Op::PopMax, 0);
+    }
+    scenarios.push(("pop_min_and_max", script));
+
+    let mut script = Script::new(256, false);
+    for &key in &[10, 20, 200] {
+        script.push(Op::Insert, key);
+    }
+    script
+        .push(Op::NearestLower, 15)
+        .push(Op::NearestHigher, 15)
+        .push(Op::NearestLower, 16)
+        .push(Op::NearestHigher, 0)
+        .push(Op::NearestLower, 255)
+        .push(Op::Ceil, 11)
+        .push(Op::Floor, 199);
+    scenarios.push(("ceil_floor_nearest", script));
+
+    let mut script = Script::new(500, false);
+    for key in (0..500).step_by(7) {
+        script.push(Op::Insert, key);
+    }
+    for key in (0..520).step_by(45) {
+        script
+            .push(Op::Rank, key)
+            .push(Op::Select, key / 7)
+            .push(Op::RangeCount, key)
+            .push(Op::Range, key);
+    }
+    scenarios.push(("rank_and_select", script));
+
+    let mut script = Script::new(5000, true);
+    for _ in 0..3 {
+        for key in (0..5000).step_by(97) {
+            script.push(Op::Insert, key);
+        }
+        for key in (0..5000).step_by(97) {
+            script.push(Op::Delete, key);
+        }
+    }
+    scenarios.push(("sparse_churn_reuses_nodes", script));
+
+    let mut script = Script::new(64, false);
+    for key in [1, 2, 3, 60] {
+        script.push(Op::Insert, key);
+    }
+    script
+        .push(Op::Take, 2)
+        .push(Op::Take, 2)
+        .push(Op::Remove, 3)
+        .push(Op::Remove, 64);
+    scenarios.push(("take_and_remove", script));
+
+    for &(name, universe) in &[
+        ("every_universe_size_0", 0),
+        ("every_universe_size_1", 1),
+        ("every_universe_size_65", 65),
+    ] {
+        let mut script = Script::new(universe, false);
+        script
+            .push(Op::TryInsert, 0)
+            .push(Op::TryInsert, 64)
+            .push(Op::FindNext, 0)
+            .push(Op::FindPrev, 64)
+            .push(Op::PopMax, 0);
+        scenarios.push((name, script));
+    }
+
+    scenarios
+}
+
+fn main() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    for target in &["ops", "snapshot", "deserialize"] {
+        fs::create_dir_all(corpus.join(target)).unwrap();
+    }
+    for (name, script) in scenarios() {
+        let tree = script.run();
+        fs::write(corpus.join("ops").join(name), script.encode()).unwrap();
+        let mut snapshot = Vec::new();
+        tree.write_to(&mut snapshot).unwrap();
+        fs::write(corpus.join("snapshot").join(name), snapshot).unwrap();
+        let json = serde_json::to_vec(&tree).unwrap();
+        fs::write(corpus.join("deserialize").join(name), json).unwrap();
+    }
+}
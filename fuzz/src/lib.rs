@@ -0,0 +1,445 @@
+//! The checks behind the fuzz targets, shared with the seed corpus
+//! generator (examples/seed_corpus.rs) and the corpus regression test
+//! (tests/corpus.rs) so that all three agree on the input format.
+//!
+//! * `ops` decodes arbitrary bytes into a universe and a sequence of
+//!   operations, runs them against a `VEBTree<u32>` and a `BTreeSet`
+//!   side by side and panics as soon as the two disagree.
+//! * `snapshot` feeds arbitrary bytes to `VEBTree::read_from`, once
+//!   as they are and once with the checksum patched to match so that
+//!   mutations get past it into the decoder.
+//! * `deserialize` feeds arbitrary bytes to the serde implementation
+//!   through serde_json.
+//!
+//! Every tree that is successfully decoded must pass `validate` and
+//! survive a round trip through both formats unchanged.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+use vebtrees::{TieBreak, VEBKey, VEBTree, VebError};
+
+/// The largest universe a dense tree is built with.  Dense trees
+/// allocate their whole universe up front, so larger requests would
+/// only measure how fast the fuzzer runs out of memory.
+pub const DENSE_LIMIT: usize = 1 << 14;
+
+/// The most keys a serialized tree may expand to before it is skipped,
+/// since runs let a few bytes of JSON describe billions of keys
+const KEY_LIMIT: u64 = 1 << 16;
+
+/// Set on the op byte of a step to wrap its key into 0..=universe, so
+/// that short inputs still hit keys that are in the tree, as well as
+/// the first key outside of it
+pub const WRAP: u8 = 0x80;
+
+/// The operations a script can run, one per public query or update of
+/// VEBTree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Insert,
+    TryInsert,
+    Delete,
+    TryDelete,
+    Remove,
+    Take,
+    Contains,
+    TryContains,
+    Search,
+    FindNext,
+    FindPrev,
+    Ceil,
+    Floor,
+    NearestLower,
+    NearestHigher,
+    Minimum,
+    Maximum,
+    PopMin,
+    PopMax,
+    Rank,
+    Select,
+    RangeCount,
+    Range,
+}
+
+impl Op {
+    /// Every op, indexed by the low bits of an op byte
+    const ALL: [Op; 23] = [
+        Op::Insert,
+        Op::TryInsert,
+        Op::Delete,
+        Op::TryDelete,
+        Op::Remove,
+        Op::Take,
+        Op::Contains,
+        Op::TryContains,
+        Op::Search,
+        Op::FindNext,
+        Op::FindPrev,
+        Op::Ceil,
+        Op::Floor,
+        Op::NearestLower,
+        Op::NearestHigher,
+        Op::Minimum,
+        Op::Maximum,
+        Op::PopMin,
+        Op::PopMax,
+        Op::Rank,
+        Op::Select,
+        Op::RangeCount,
+        Op::Range,
+    ];
+
+    fn from_byte(byte: u8) -> Op {
+        Op::ALL[usize::from(byte & !WRAP) % Op::ALL.len()]
+    }
+
+    fn to_byte(self) -> u8 {
+        Op::ALL.iter().position(|&op| op == self).unwrap() as u8
+    }
+}
+
+/// A tree layout and the operations to run on it, as decoded from the
+/// input of the ops target
+///
+/// The input is a flags byte (bit 0 set for a sparse tree), the
+/// universe as a little endian u32, then five bytes per step: an op
+/// byte and a little endian u32 key.  A trailing partial step is
+/// ignored.  Dense universes are taken modulo DENSE_LIMIT + 1, sparse
+/// ones as they are except that u32::MAX stands for the whole key
+/// type, 2^32.
+#[derive(Clone, Debug)]
+pub struct Script {
+    pub universe: usize,
+    pub sparse: bool,
+    pub steps: Vec<(u8, u32)>,
+}
+
+impl Script {
+    pub fn new(universe: usize, sparse: bool) -> Self {
+        Script {
+            universe,
+            sparse,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Appends a step with its key taken as is
+    pub fn push(&mut self, op: Op, key: u32) -> &mut Self {
+        self.steps.push((op.to_byte(), key));
+        self
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Script> {
+        let (&flags, rest) = data.split_first()?;
+        let (universe, rest) = rest.split_at_checked(4)?;
+        let universe = u32::from_le_bytes(universe.try_into().unwrap());
+        let sparse = flags & 1 != 0;
+        let universe = if !sparse {
+            universe as usize % (DENSE_LIMIT + 1)
+        } else if universe == u32::MAX {
+            1 << 32
+        } else {
+            universe as usize
+        };
+        let steps = rest
+            .chunks_exact(5)
+            .map(|step| (step[0], u32::from_le_bytes(step[1..].try_into().unwrap())))
+            .collect();
+        Some(Script {
+            universe,
+            sparse,
+            steps,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let universe = if self.universe == 1 << 32 {
+            u32::MAX
+        } else {
+            self.universe as u32
+        };
+        let mut data = vec![self.sparse as u8];
+        data.extend_from_slice(&universe.to_le_bytes());
+        for &(op, key) in &self.steps {
+            data.push(op);
+            data.extend_from_slice(&key.to_le_bytes());
+        }
+        data
+    }
+
+    /// Runs every step against a fresh tree and a BTreeSet, then
+    ///     checks the final tree in full
+    ///
+    /// # Returns
+    /// * The final tree, which the corpus generator snapshots
+    ///
+    /// # Panics
+    /// * At the first step where the tree and the BTreeSet disagree
+    pub fn run(&self) -> VEBTree<u32> {
+        let mut tree = if self.sparse {
+            VEBTree::new_sparse(self.universe)
+        } else {
+            VEBTree::new(self.universe)
+        };
+        let mut reference = BTreeSet::new();
+        for &(byte, key) in &self.steps {
+            let key = if byte & WRAP != 0 {
+                (u64::from(key) % (self.universe as u64 + 1)) as u32
+            } else {
+                key
+            };
+            let op = Op::from_byte(byte);
+            check_step(&mut tree, &mut reference, op, key);
+            assert_eq!(tree.len(), reference.len(), "len after {:?} {}", op, key);
+        }
+        assert!(tree.iter().eq(reference.iter().cloned()));
+        assert!(tree.iter().rev().eq(reference.iter().rev().cloned()));
+        check_tree(&tree);
+        tree
+    }
+}
+
+/// Applies one operation to both the tree and the BTreeSet, checking
+///     that they give the same answer
+fn check_step(tree: &mut VEBTree<u32>, reference: &mut BTreeSet<u32>, op: Op, key: u32) {
+    let universe = tree.universe();
+    // Only used for keys past max_key, which leaves room for one more
+    let out_of_range = VebError::OutOfRange {
+        key,
+        universe: tree.max_key().map_or(0, |max_key| max_key.wrapping_add(1)),
+    };
+    let in_range = (key as usize) < universe;
+    match op {
+        Op::Insert => {
+            // insert panics outside of the universe by design, so the
+            // key is wrapped into it
+            if universe > 0 {
+                let key = (key as usize % universe) as u32;
+                assert_eq!(tree.insert(key), reference.insert(key), "{:?} {}", op, key);
+            }
+        }
+        Op::TryInsert => {
+            let expected = if in_range {
+                Ok(reference.insert(key))
+            } else {
+                Err(out_of_range)
+            };
+            assert_eq!(tree.try_insert(key), expected, "{:?} {}", op, key);
+        }
+        Op::Delete => assert_eq!(tree.delete(key), reference.remove(&key), "{:?} {}", op, key),
+        Op::TryDelete => {
+            let expected = if in_range {
+                Ok(reference.remove(&key))
+            } else {
+                Err(out_of_range)
+            };
+            assert_eq!(tree.try_delete(key), expected, "{:?} {}", op, key);
+        }
+        Op::Remove => assert_eq!(tree.remove(key), reference.remove(&key), "{:?} {}", op, key),
+        Op::Take => assert_eq!(tree.take(key), reference.take(&key), "{:?} {}", op, key),
+        Op::Contains => assert_eq!(tree.contains(key), reference.contains(&key), "{:?} {}", op, key),
+        Op::TryContains => {
+            let expected = if in_range {
+                Ok(reference.contains(&key))
+            } else {
+                Err(out_of_range)
+            };
+            assert_eq!(tree.try_contains(key), expected, "{:?} {}", op, key);
+        }
+        Op::Search => assert_eq!(tree.search(key), reference.get(&key).cloned(), "{:?} {}", op, key),
+        Op::FindNext => {
+            let expected = reference
+                .range((Bound::Excluded(key), Bound::Unbounded))
+                .next()
+                .cloned();
+            assert_eq!(tree.findnext(key), expected, "{:?} {}", op, key);
+        }
+        Op::FindPrev => {
+            let expected = reference.range(..key).next_back().cloned();
+            assert_eq!(tree.findprev(key), expected, "{:?} {}", op, key);
+        }
+        Op::Ceil => {
+            let expected = reference.range(key..).next().cloned();
+            assert_eq!(tree.ceil(key), expected, "{:?} {}", op, key);
+        }
+        Op::Floor => {
+            let expected = reference.range(..=key).next_back().cloned();
+            assert_eq!(tree.floor(key), expected, "{:?} {}", op, key);
+        }
+        Op::NearestLower | Op::NearestHigher => {
+            let tie_break = if op == Op::NearestLower {
+                TieBreak::Lower
+            } else {
+                TieBreak::Higher
+            };
+            let below = reference.range(..=key).next_back().cloned();
+            let above = reference.range(key..).next().cloned();
+            let expected = match (below, above) {
+                (Some(below), Some(above)) => {
+                    let (down, up) = (key - below, above - key);
+                    if down < up || (down == up && tie_break == TieBreak::Lower) {
+                        Some(below)
+                    } else {
+                        Some(above)
+                    }
+                }
+                (below, above) => below.or(above),
+            };
+            assert_eq!(tree.nearest(key, tie_break), expected, "{:?} {}", op, key);
+        }
+        Op::Minimum => assert_eq!(tree.minimum(), reference.first().cloned()),
+        Op::Maximum => assert_eq!(tree.maximum(), reference.last().cloned()),
+        Op::PopMin => assert_eq!(tree.pop_min(), reference.pop_first()),
+        Op::PopMax => assert_eq!(tree.pop_max(), reference.pop_last()),
+        Op::Rank => assert_eq!(tree.rank(key), reference.range(..key).count(), "{:?} {}", op, key),
+        Op::Select => {
+            let expected = reference.iter().nth(key as usize).cloned();
+            assert_eq!(tree.select(key as usize), expected, "{:?} {}", op, key);
+        }
+        Op::RangeCount => {
+            let expected = reference.range(..key).count();
+            assert_eq!(tree.range_count(..key), expected, "{:?} {}", op, key);
+        }
+        Op::Range => {
+            assert!(tree.range(key..).eq(reference.range(key..).cloned()), "{:?} {}", op, key);
+            assert!(tree.range(..=key).rev().eq(reference.range(..=key).rev().cloned()), "{:?} {}", op, key);
+        }
+    }
+}
+
+/// Checks a tree that was built or decoded successfully: its structure
+///     must be valid and both formats must give it back unchanged
+pub fn check_tree<K: VEBKey + Serialize + DeserializeOwned>(tree: &VEBTree<K>) {
+    assert_eq!(tree.validate(), Ok(()));
+    let mut snapshot = Vec::new();
+    tree.write_to(&mut snapshot).unwrap();
+    assert_eq!(&VEBTree::<K>::read_from(&snapshot[..]).unwrap(), tree);
+    let json = serde_json::to_vec(tree).unwrap();
+    assert_eq!(&serde_json::from_slice::<VEBTree<K>>(&json).unwrap(), tree);
+}
+
+/// The input of the ops target
+pub fn run_ops(data: &[u8]) {
+    if let Some(script) = Script::decode(data) {
+        script.run();
+    }
+}
+
+/// Where the max key varint starts in a snapshot, see src/snapshot.rs.
+/// Everything after it moves with the length of the varint.
+const MAX_KEY_AT: usize = 8;
+
+/// Finds where the payload length of a snapshot is, the payload
+/// follows it.  None if the input is too short or the max key is not
+/// a varint.
+fn payload_len_at(data: &[u8]) -> Option<usize> {
+    let mut max_key_len = 0;
+    loop {
+        let byte = *data.get(MAX_KEY_AT + max_key_len)?;
+        max_key_len += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if max_key_len > 18 {
+            return None;
+        }
+    }
+    // len and encoding sit between the max key and the payload length
+    Some(MAX_KEY_AT + max_key_len + 9)
+}
+
+/// The input of the snapshot target
+pub fn run_snapshot(data: &[u8]) {
+    read_snapshot(data);
+    if let Some(repaired) = with_checksum_repaired(data) {
+        read_snapshot(&repaired);
+    }
+}
+
+/// Reads a snapshot as each of a few key widths, checking any tree
+///     that comes out of it
+fn read_snapshot(data: &[u8]) {
+    if let Ok(tree) = VEBTree::<u8>::read_from(data) {
+        check_tree(&tree);
+    }
+    if let Ok(tree) = VEBTree::<u32>::read_from(data) {
+        check_tree(&tree);
+    }
+    if let Ok(tree) = VEBTree::<u64>::read_from(data) {
+        check_tree(&tree);
+    }
+}
+
+/// Rewrites the checksum of a snapshot to match the bytes before it
+///
+/// # Returns
+/// * A copy of the input with the checksum patched, None if the input
+///   is too short to hold the payload it declares and a checksum
+fn with_checksum_repaired(data: &[u8]) -> Option<Vec<u8>> {
+    let payload_len_at = payload_len_at(data)?;
+    let payload_len = data.get(payload_len_at..payload_len_at + 8)?;
+    let payload_len = u64::from_le_bytes(payload_len.try_into().unwrap());
+    let end = usize::try_from(payload_len).ok()?.checked_add(payload_len_at + 8)?;
+    data.get(end..end.checked_add(4)?)?;
+    let mut repaired = data.to_vec();
+    repaired[end..end + 4].copy_from_slice(&crc32(&data[..end]).to_le_bytes());
+    Some(repaired)
+}
+
+/// The reflected CRC-32 (IEEE 802.3) that snapshots are checked with
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// The input of the deserialize target
+pub fn run_deserialize(data: &[u8]) {
+    let value: serde_json::Value = match serde_json::from_slice(data) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    if !within_limits(&value) {
+        return;
+    }
+    if let Ok(tree) = serde_json::from_slice::<VEBTree<u8>>(data) {
+        check_tree(&tree);
+    }
+    if let Ok(tree) = serde_json::from_slice::<VEBTree<u32>>(data) {
+        check_tree(&tree);
+    }
+    if let Ok(tree) = serde_json::from_slice::<VEBTree<u64>>(data) {
+        check_tree(&tree);
+    }
+}
+
+/// Whether a serialized tree is small enough to build quickly: runs
+///     covering at most KEY_LIMIT keys.  Dense universes need no limit
+///     here, deserializing rebuilds those above MAX_LOADED_DENSE_UNIVERSE
+///     sparse.
+fn within_limits(value: &serde_json::Value) -> bool {
+    let covered: u64 = value["contents"]["Runs"]
+        .as_array()
+        .map(|runs| {
+            runs.iter()
+                .map(|run| {
+                    let first = run[0].as_u64().unwrap_or(0);
+                    let last = run[1].as_u64().unwrap_or(0);
+                    last.saturating_sub(first).saturating_add(1)
+                })
+                .fold(0, u64::saturating_add)
+        })
+        .unwrap_or(0);
+    covered <= KEY_LIMIT
+}
@@ -0,0 +1,31 @@
+//! Runs every file of the seed corpus through its target, so that the
+//! corpus keeps passing as the crate changes without needing a fuzzer.
+
+use std::fs;
+use std::path::Path;
+
+fn run_corpus(target: &str, run: fn(&[u8])) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus").join(target);
+    let mut count = 0;
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        run(&fs::read(&path).unwrap());
+        count += 1;
+    }
+    assert!(count > 0, "no seeds in {}", dir.display());
+}
+
+#[test]
+fn ops_corpus() {
+    run_corpus("ops", vebtrees_fuzz::run_ops);
+}
+
+#[test]
+fn snapshot_corpus() {
+    run_corpus("snapshot", vebtrees_fuzz::run_snapshot);
+}
+
+#[test]
+fn deserialize_corpus() {
+    run_corpus("deserialize", vebtrees_fuzz::run_deserialize);
+}
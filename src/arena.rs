@@ -0,0 +1,1928 @@
+use std::cmp;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+
+use error::InvariantViolation;
+use key::{bit_length, universe_size, VEBKey};
+use stats::{LevelStats, TreeStats};
+
+/// The number of key bits stored directly in a bitmap leaf, one bit
+///     per key of a u64 word
+const LEAF_BITS: u32 = 6;
+
+/// The largest universe stored directly as a bitmap leaf
+const LEAF_SIZE: usize = 1 << LEAF_BITS;
+
+/// The id of the top level of the recursion, which is never freed
+const ROOT: usize = 0;
+
+/// Where the clusters (children) of a node are kept in the arena.
+///
+/// # Variants
+/// * Leaf: the node has no clusters, its keys are in its bitmap
+/// * Dense: every cluster is allocated up front, as a contiguous run
+///   of nodes starting at the given id
+/// * Sparse: only non-empty clusters are allocated, mapping their
+///   cluster number to their id.  Clusters are created on the first
+///   insert into them and freed again when they become empty.
+#[derive(Clone, Debug)]
+enum Clusters<K: VEBKey> {
+    Leaf,
+    Dense(usize),
+    Sparse(HashMap<K, usize>),
+}
+
+/// Running totals of the elements stored in each cluster of a node, as
+///     a Fenwick tree over the cluster indices.
+///
+/// Position p, counting clusters from 1, holds the total of the
+///     clusters p - lowbit(p) + 1 through p, where lowbit(p) is the
+///     lowest set bit of p.  Adding to one cluster or totalling the
+///     clusters before one both visit one position per bit of the
+///     cluster index, so rank and select find how many elements come
+///     before a cluster without walking the clusters themselves.
+///
+/// # Variants
+/// * None: the node is a leaf, or belongs to an aux arena, which is
+///   never ranked
+/// * Dense: every position, kept in the totals of the arena from the
+///   given offset on, the way a dense node keeps its clusters
+/// * Sparse: only the positions with a non-zero total, so the counts
+///   of a sparse node grow with its populated clusters rather than
+///   with its universe
+#[derive(Clone, Debug)]
+enum Counts<K: VEBKey> {
+    None,
+    Dense(usize),
+    Sparse(HashMap<K, usize>),
+}
+
+/// Returns the lowest set bit of a non-zero key
+fn lowest_bit<K: VEBKey>(value: K) -> K {
+    value - (value & (value - K::ONE))
+}
+
+impl<K: VEBKey> Counts<K> {
+    /// Returns the total held at a position, 0 for positions a sparse
+    ///     node has no entry for
+    ///
+    /// # Arguments
+    /// * self: the counts to read
+    /// * totals: the totals of the arena, holding those of dense nodes
+    /// * position: the position to read, from 1
+    fn get(&self, totals: &[usize], position: K) -> usize {
+        match *self {
+            Counts::None => 0,
+            Counts::Dense(offset) => totals[offset + position.to_usize() - 1],
+            Counts::Sparse(ref totals) => totals.get(&position).cloned().unwrap_or(0),
+        }
+    }
+
+    /// Adds to or takes from the count of one cluster
+    ///
+    /// # Arguments
+    /// * self: the counts to update
+    /// * totals: the totals of the arena, holding those of dense nodes
+    /// * cluster: the index of the cluster
+    /// * end: the number of clusters of the node
+    /// * amount: how many elements were added or removed
+    /// * added: whether the elements were added
+    fn update(&mut self, totals: &mut [usize], cluster: K, end: K, amount: usize, added: bool) {
+        let mut position = cluster + K::ONE;
+        while position <= end {
+            match *self {
+                Counts::None => return,
+                Counts::Dense(offset) => {
+                    let total = &mut totals[offset + position.to_usize() - 1];
+                    if added {
+                        *total += amount;
+                    } else {
+                        *total -= amount;
+                    }
+                }
+                Counts::Sparse(ref mut totals) => {
+                    if added {
+                        *totals.entry(position).or_insert(0) += amount;
+                    } else {
+                        let total = totals
+                            .get_mut(&position)
+                            .expect("Data structure appears corrupt");
+                        *total -= amount;
+                        if *total == 0 {
+                            totals.remove(&position);
+                            if totals.len() * 4 < totals.capacity() {
+                                totals.shrink_to_fit();
+                            }
+                        }
+                    }
+                }
+            }
+            position = position + lowest_bit(position);
+        }
+    }
+
+    /// Totals the clusters before a given one
+    ///
+    /// # Arguments
+    /// * self: the counts to read
+    /// * totals: the totals of the arena, holding those of dense nodes
+    /// * cluster: the index of the cluster to count up to
+    ///
+    /// # Returns
+    /// * The number of elements in clusters with a smaller index
+    fn before(&self, totals: &[usize], cluster: K) -> usize {
+        let mut position = cluster;
+        let mut total = 0;
+        while position != K::ZERO {
+            total += self.get(totals, position);
+            position = position & (position - K::ONE);
+        }
+        total
+    }
+
+    /// Finds the cluster holding the element with a given number of
+    ///     smaller elements among all of the clusters, by descending
+    ///     the implicit tree one bit of the cluster index at a time
+    ///
+    /// # Arguments
+    /// * self: the counts to search
+    /// * totals: the totals of the arena, holding those of dense nodes
+    /// * rank: the 0-based position of the element, smaller than the
+    ///   total of every cluster
+    /// * end: the number of clusters of the node
+    ///
+    /// # Returns
+    /// * The index of the cluster and the rank of the element within it
+    fn find(&self, totals: &[usize], mut rank: usize, end: K) -> (K, usize) {
+        let mut position = K::ZERO;
+        let mut step = K::ONE << (bit_length(end) - 1);
+        loop {
+            let next = position + step;
+            if next <= end {
+                let total = self.get(totals, next);
+                if total <= rank {
+                    position = next;
+                    rank -= total;
+                }
+            }
+            if step == K::ONE {
+                return (position, rank);
+            }
+            step = step >> 1;
+        }
+    }
+
+    /// Checks the counts against the number of elements in each
+    ///     cluster, rebuilding them in one visit per position so that
+    ///     validation stays linear
+    ///
+    /// # Arguments
+    /// * self: the counts to check
+    /// * totals: the totals of the arena, holding those of dense nodes
+    /// * lens: the index and element count of each allocated cluster
+    /// * end: the number of clusters of the node
+    ///
+    /// # Returns
+    /// * Whether the counts match 'lens'
+    fn matches(&self, totals: &[usize], lens: &[(K, usize)], end: K) -> bool {
+        match *self {
+            Counts::None => true,
+            Counts::Dense(offset) => {
+                // Index i holds position i + 1
+                let mut tally = vec![0; end.to_usize()];
+                for &(cluster, len) in lens {
+                    tally[cluster.to_usize()] += len;
+                }
+                for index in 0..tally.len() {
+                    let position = index + 1;
+                    let parent = position + (position & position.wrapping_neg());
+                    if parent <= tally.len() {
+                        tally[parent - 1] += tally[index];
+                    }
+                }
+                totals.get(offset..offset + tally.len()) == Some(&tally[..])
+            }
+            Counts::Sparse(ref totals) => {
+                let mut pending: BTreeMap<K, usize> = lens
+                    .iter()
+                    .filter(|&&(_, len)| len != 0)
+                    .map(|&(cluster, len)| (cluster + K::ONE, len))
+                    .collect();
+                let mut tally = HashMap::new();
+                while let Some((position, total)) = pending.pop_first() {
+                    let parent = position + lowest_bit(position);
+                    if parent <= end {
+                        *pending.entry(parent).or_insert(0) += total;
+                    }
+                    tally.insert(position, total);
+                }
+                tally == *totals
+            }
+        }
+    }
+
+    /// Returns the heap bytes held by the counts themselves, which
+    ///     for a dense node are part of the totals of the arena
+    fn heap_size_bytes(&self) -> usize {
+        match *self {
+            Counts::None | Counts::Dense(_) => 0,
+            Counts::Sparse(ref totals) => totals.capacity() * (mem::size_of::<(K, usize)>() + 1),
+        }
+    }
+}
+
+/// A single level of the Van Emde Boas recursion, stored in an Arena.
+///
+/// The minimum of a node is kept in the node itself and is not
+///     stored again in any of its clusters, which is what keeps
+///     inserting into an empty cluster a constant time operation.
+///     The maximum is cached, but is also stored in the clusters
+///     unless it is equal to the minimum.
+///
+/// Larger universes are split on a bit boundary: the low bits of a key
+///     are its offset within a cluster and the high bits pick the
+///     cluster, so high and low are a shift and a mask.  Each cluster
+///     covers 2^low_bits keys except for the last, which only covers
+///     what is left of the universe, so no level allocates more
+///     clusters than it needs.
+///
+/// Universes of up to LEAF_SIZE keys are not split any further, they
+///     are leaves that keep every key (the minimum included) as a bit
+///     of a u64, with successors and predecessors found by counting
+///     zero bits.
+///
+/// # Fields
+/// * last: K - the largest key the node can hold, so its universe
+///   is last + 1 keys.  Kept as a key rather than a count so that a
+///   node can cover every value of K.
+/// * low_bits: u32 - the number of low key bits that index within a
+///   cluster, 0 for a leaf
+/// * clusters: `Clusters<K>` - where the child nodes of this node are
+/// * aux: `Option<usize>` - the id of the aux tree of populated cluster
+///   indices in the aux arena.  None for a leaf and for a sparse node
+///   with no populated clusters.
+/// * max: `Option<K>` - The maximum of the currently stored elements,
+///   none if there are no stored elements, equal to min if there is
+///   only one element
+/// * min: `Option<K>` - The minimum of the currently stored elements,
+///   none if there are no stored elements, equal to max if there is
+///   only one element
+/// * min_value: `Option<V>` - the value stored with min, None in a
+///   leaf
+/// * bits: u64 - the keys stored in a leaf, bit i set when i is
+///   present, 0 everywhere else
+/// * values: `Vec<V>` - the values stored in a leaf in ascending key
+///   order, empty everywhere else
+/// * len: usize - the number of elements stored in this node,
+///   including min
+/// * counts: `Counts<K>` - the running totals of the elements in each
+///   cluster, which leave out min like the clusters do
+#[derive(Clone, Debug)]
+struct Node<K: VEBKey, V> {
+    last: K,
+    low_bits: u32,
+    clusters: Clusters<K>,
+    counts: Counts<K>,
+    aux: Option<usize>,
+    max: Option<K>,
+    min: Option<K>,
+    min_value: Option<V>,
+    bits: u64,
+    values: Vec<V>,
+    len: usize,
+}
+
+impl<K: VEBKey, V> Node<K, V> {
+    /// Creates a new empty node, without any clusters allocated yet.
+    ///
+    /// # Arguments
+    /// * last: the largest key the node can hold
+    /// * sparse: whether clusters should be allocated lazily
+    /// * counted: whether the node keeps counts of its clusters
+    fn empty(last: K, sparse: bool, counted: bool) -> Self {
+        let low_bits = if last.to_usize() < LEAF_SIZE {
+            0
+        } else {
+            // Half of the bits needed for the largest key, rounded
+            // down, but never less than a whole leaf
+            cmp::max(bit_length(last) / 2, LEAF_BITS)
+        };
+        let clusters = if low_bits == 0 {
+            Clusters::Leaf
+        } else if sparse {
+            Clusters::Sparse(HashMap::new())
+        } else {
+            Clusters::Dense(ROOT)
+        };
+        // The counts of a dense node are placed by the arena along
+        // with its clusters
+        let counts = if low_bits != 0 && counted && sparse {
+            Counts::Sparse(HashMap::new())
+        } else {
+            Counts::None
+        };
+        Node {
+            last,
+            low_bits,
+            clusters,
+            counts,
+            aux: None,
+            max: None,
+            min: None,
+            min_value: None,
+            bits: 0,
+            values: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Whether this node is the bottom of the recursion, holding its
+    ///     elements in a bitmap.
+    fn is_leaf(&self) -> bool {
+        self.low_bits == 0
+    }
+
+    /// Returns the index of the node's last cluster, which is also the
+    ///     largest key of its aux tree
+    fn cluster_last(&self) -> K {
+        self.high(self.last)
+    }
+
+    /// Returns the number of clusters of the node as a key, which is
+    ///     the last position of its counts
+    fn cluster_end(&self) -> K {
+        self.cluster_last() + K::ONE
+    }
+
+    /// Returns the number of clusters of the node, saturating at
+    ///     usize::MAX for sparse nodes with more clusters than that
+    fn cluster_count(&self) -> usize {
+        if self.is_leaf() {
+            0
+        } else {
+            self.cluster_last().to_usize().saturating_add(1)
+        }
+    }
+
+    /// Returns the largest key of one of the node's clusters, which is
+    ///     2^low_bits - 1 for all but the last
+    ///
+    /// # Arguments
+    /// * self: the instance of the Node
+    /// * cluster: the index of the cluster, at most cluster_last
+    fn cluster_max(&self, cluster: K) -> K {
+        if cluster == self.cluster_last() {
+            self.low(self.last)
+        } else {
+            (K::ONE << self.low_bits) - K::ONE
+        }
+    }
+
+    /// Returns the cluster a value belongs in, its high bits.
+    /// # Arguments
+    /// * self: the instance of the Node
+    /// * value: the value to split
+    ///
+    /// # Returns
+    /// * value >> self.low_bits
+    fn high(&self, value: K) -> K {
+        value >> self.low_bits
+    }
+
+    /// Returns the offset of a value within its cluster, its low bits.
+    /// # Arguments
+    /// * self: &Self - the instance of the Node
+    /// * value: K - the value to split
+    ///
+    /// # Returns
+    /// * value with everything above self.low_bits masked off
+    fn low(&self, value: K) -> K {
+        value & ((K::ONE << self.low_bits) - K::ONE)
+    }
+
+    /// Recombines a cluster index and an offset within that cluster
+    ///     into a value, the inverse of high and low.
+    ///
+    /// # Arguments
+    /// * self: the instance of the Node
+    /// * cluster: the index of the cluster
+    /// * offset: the value within the cluster
+    ///
+    /// # Returns
+    /// * (cluster << self.low_bits) + offset
+    fn index(&self, cluster: K, offset: K) -> K {
+        (cluster << self.low_bits) + offset
+    }
+
+    /// Returns the bit of a leaf's bitmap that stands for a key
+    ///
+    /// # Arguments
+    /// * key: a key smaller than LEAF_SIZE
+    fn leaf_bit(key: K) -> u64 {
+        1 << key.to_usize()
+    }
+
+    /// Returns the position of a key's value in the values of a leaf,
+    ///     which is the number of stored keys smaller than it
+    ///
+    /// # Arguments
+    /// * self: the leaf to operate on
+    /// * key: a key smaller than LEAF_SIZE
+    fn leaf_slot(&self, key: K) -> usize {
+        (self.bits & (Self::leaf_bit(key) - 1)).count_ones() as usize
+    }
+
+    /// Returns whether a leaf holds a key
+    ///
+    /// # Arguments
+    /// * self: the leaf to operate on
+    /// * key: the key to look for, which may be outside of the leaf
+    fn leaf_contains(&self, key: K) -> bool {
+        self.max.is_some_and(|max| key <= max) && self.bits & Self::leaf_bit(key) != 0
+    }
+
+    /// Recomputes the cached minimum and maximum of a leaf from its
+    ///     bitmap
+    fn update_leaf_bounds(&mut self) {
+        if self.bits == 0 {
+            self.min = None;
+            self.max = None;
+        } else {
+            self.min = Some(K::from_usize(self.bits.trailing_zeros() as usize));
+            self.max = Some(K::from_usize(63 - self.bits.leading_zeros() as usize));
+        }
+    }
+
+    /// Stores a key and value in a leaf.
+    ///
+    /// # Arguments
+    /// * self: the leaf to operate on
+    /// * key: the key to insert, smaller than LEAF_SIZE
+    /// * value: the value to store with the key
+    ///
+    /// # Returns
+    /// * The value previously stored with 'key', if any
+    fn insert_into_leaf(&mut self, key: K, value: V) -> Option<V> {
+        let slot = self.leaf_slot(key);
+        if self.bits & Self::leaf_bit(key) != 0 {
+            return Some(mem::replace(&mut self.values[slot], value));
+        }
+        self.bits |= Self::leaf_bit(key);
+        self.values.insert(slot, value);
+        self.update_leaf_bounds();
+        self.len += 1;
+        None
+    }
+
+    /// Removes a key from a leaf.
+    ///
+    /// # Arguments
+    /// * self: the leaf to operate on
+    /// * key: the key to remove
+    ///
+    /// # Returns
+    /// * The value that was stored with 'key', None if the key was
+    ///   not in the leaf
+    fn remove_from_leaf(&mut self, key: K) -> Option<V> {
+        if !self.leaf_contains(key) {
+            return None;
+        }
+        let slot = self.leaf_slot(key);
+        self.bits &= !Self::leaf_bit(key);
+        self.update_leaf_bounds();
+        self.len -= 1;
+        Some(self.values.remove(slot))
+    }
+}
+
+/// The storage behind VEBTree and VEBMap: every node of the recursion
+///     in one contiguous buffer, addressed by id.
+///
+/// Nodes refer to their clusters by id, and dense nodes to their
+///     cluster counts by offset into the arena's totals, instead of
+///     owning them.  A dense tree is then a single allocation of
+///     nodes per level of aux nesting plus one of totals, and cloning
+///     or dropping it is a copy or free of those buffers
+///     rather than a walk over millions of small allocations.  The
+///     aux trees of the nodes, which hold no values, live in a second
+///     arena that in turn keeps their aux trees in a third, and so on
+///     for the few levels the recursion goes.
+///
+/// Arenas compare equal when they have the same layout and hold the
+///     same keys and values, wherever the nodes happen to sit in the
+///     buffer.
+///
+/// # Fields
+/// * sparse: bool - whether clusters are allocated lazily
+/// * counted: bool - whether nodes keep counts of their clusters for
+///   rank and select, which only the top arena needs
+/// * nodes: `Vec<Node<K, V>>` - every node, the top level of the
+///   recursion at ROOT
+/// * free: `Vec<usize>` - ids of nodes freed by a sparse arena, to be
+///   reused before the buffer grows, the buffer is compacted once they
+///   make up more than half of it
+/// * totals: `Vec<usize>` - the cluster counts of every dense node,
+///   each node's in one contiguous run
+/// * aux: `Option<Box<Arena<K, ()>>>` - the arena holding the aux trees
+///   of the nodes, None until one is needed
+#[derive(Clone, Debug)]
+pub struct Arena<K: VEBKey, V> {
+    sparse: bool,
+    counted: bool,
+    nodes: Vec<Node<K, V>>,
+    free: Vec<usize>,
+    totals: Vec<usize>,
+    aux: Option<Box<Arena<K, ()>>>,
+}
+
+impl<K: VEBKey, V> Arena<K, V> {
+    /// Creates a new empty arena for the keys up to and including
+    ///     last, allocating every level of the recursion up front.
+    ///
+    /// # Arguments
+    /// * last: the largest key the arena can hold
+    ///
+    /// # Returns
+    /// * An arena for the keys 0..=last
+    pub fn new(last: K) -> Self {
+        Self::with_layout(last, false)
+    }
+
+    /// Creates a new empty arena for the keys up to and including
+    ///     last that only allocates clusters once something is stored
+    ///     in them.
+    ///
+    /// # Arguments
+    /// * last: the largest key the arena can hold
+    ///
+    /// # Returns
+    /// * An arena for the keys 0..=last
+    pub fn new_sparse(last: K) -> Self {
+        Self::with_layout(last, true)
+    }
+
+    /// Shared constructor for the dense and sparse layouts
+    ///
+    /// # Arguments
+    /// * last: the largest key the arena can hold
+    /// * sparse: whether clusters should be allocated lazily
+    ///
+    /// # Returns
+    /// * An arena for the keys 0..=last
+    fn with_layout(last: K, sparse: bool) -> Self {
+        let mut arena = Self::empty(sparse, true);
+        arena.alloc(last);
+        arena
+    }
+
+    /// Creates an arena without any nodes in it
+    fn empty(sparse: bool, counted: bool) -> Self {
+        Arena {
+            sparse,
+            counted,
+            nodes: Vec::new(),
+            free: Vec::new(),
+            totals: Vec::new(),
+            aux: None,
+        }
+    }
+
+    /// Whether this arena allocates its clusters lazily.
+    pub fn is_sparse(&self) -> bool {
+        self.sparse
+    }
+
+    /// Adds an empty node to the arena, reusing a freed id if there is
+    ///     one.  In a dense arena all of the node's clusters and its
+    ///     aux tree are allocated along with it.
+    ///
+    /// # Arguments
+    /// * self: the arena to allocate in
+    /// * last: the largest key of the new node
+    ///
+    /// # Returns
+    /// * The id of the new node
+    fn alloc(&mut self, last: K) -> usize {
+        let node = Node::empty(last, self.sparse, self.counted);
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        if !self.sparse {
+            self.alloc_clusters(id);
+        }
+        id
+    }
+
+    /// Allocates every cluster of a dense node as one contiguous run,
+    ///     then their clusters in turn, and the node's aux tree.
+    fn alloc_clusters(&mut self, id: usize) {
+        let count = self.nodes[id].cluster_count();
+        if count == 0 {
+            return;
+        }
+        let first = self.nodes.len();
+        for cluster in 0..count {
+            let last = self.nodes[id].cluster_max(K::from_usize(cluster));
+            self.nodes.push(Node::empty(last, false, self.counted));
+        }
+        for child in first..first + count {
+            self.alloc_clusters(child);
+        }
+        self.nodes[id].clusters = Clusters::Dense(first);
+        if self.counted {
+            let offset = self.totals.len();
+            self.totals.resize(offset + count, 0);
+            self.nodes[id].counts = Counts::Dense(offset);
+        }
+        let cluster_last = self.nodes[id].cluster_last();
+        let aux = self.aux_arena().alloc(cluster_last);
+        self.nodes[id].aux = Some(aux);
+    }
+
+    /// Returns a node that is no longer used to a sparse arena, so
+    ///     that its id can be handed out again.  The node is reset so
+    ///     that it holds on to no memory, and free nodes at the end of
+    ///     the buffer are dropped from it straight away.
+    fn release(&mut self, id: usize) {
+        // A bare leaf owns nothing on the heap
+        self.nodes[id] = Node::empty(K::ZERO, false, false);
+        self.free.push(id);
+        while self
+            .free
+            .last()
+            .is_some_and(|&last| last + 1 == self.nodes.len())
+        {
+            self.free.pop();
+            self.nodes.pop();
+        }
+    }
+
+    /// Gives the memory of freed nodes back once they are more than
+    ///     half of the buffer, after doing the same for the aux arenas.
+    ///     The live nodes move down to close the gaps, keeping their
+    ///     order so that ROOT stays where it is.
+    ///
+    /// # Arguments
+    /// * self: the arena to compact
+    ///
+    /// # Returns
+    /// * The new id of each old id, usize::MAX for the freed ones,
+    ///   None if the nodes did not move
+    fn reclaim(&mut self) -> Option<Vec<usize>> {
+        if let Some(aux_arena) = self.aux.as_mut() {
+            if let Some(renumbered) = aux_arena.reclaim() {
+                for node in &mut self.nodes {
+                    if let Some(ref mut aux) = node.aux {
+                        *aux = renumbered[*aux];
+                    }
+                }
+            }
+        }
+        if self
+            .aux
+            .as_ref()
+            .is_some_and(|aux_arena| aux_arena.nodes.is_empty())
+        {
+            self.aux = None;
+        }
+        if self.free.len() * 2 <= self.nodes.len() {
+            return None;
+        }
+        let mut renumbered = vec![0; self.nodes.len()];
+        for &id in &self.free {
+            renumbered[id] = usize::MAX;
+        }
+        let live = renumbered
+            .iter_mut()
+            .filter(|new_id| **new_id != usize::MAX);
+        for (next, new_id) in live.enumerate() {
+            *new_id = next;
+        }
+        let mut old_id = 0;
+        self.nodes.retain(|_| {
+            old_id += 1;
+            renumbered[old_id - 1] != usize::MAX
+        });
+        self.nodes.shrink_to_fit();
+        self.free = Vec::new();
+        for node in &mut self.nodes {
+            if let Clusters::Sparse(ref mut children) = node.clusters {
+                for child in children.values_mut() {
+                    *child = renumbered[*child];
+                }
+            }
+        }
+        Some(renumbered)
+    }
+
+    /// Returns the arena holding the aux trees, creating it first if
+    ///     there is none yet.
+    fn aux_arena(&mut self) -> &mut Arena<K, ()> {
+        let sparse = self.sparse;
+        self.aux
+            .get_or_insert_with(|| Box::new(Arena::empty(sparse, false)))
+    }
+
+    /// Looks up a cluster by its index
+    ///
+    /// # Arguments
+    /// * self: the arena to look in
+    /// * id: the node whose cluster to find
+    /// * cluster: the index of the cluster
+    ///
+    /// # Returns
+    /// * The id of the cluster, None if it is out of range or has not
+    ///   been allocated
+    fn cluster(&self, id: usize, cluster: K) -> Option<usize> {
+        let node = &self.nodes[id];
+        match node.clusters {
+            Clusters::Leaf => None,
+            Clusters::Dense(first) if cluster <= node.cluster_last() => {
+                Some(first + cluster.to_usize())
+            }
+            Clusters::Dense(_) => None,
+            Clusters::Sparse(ref children) => children.get(&cluster).cloned(),
+        }
+    }
+
+    /// Looks up a cluster by its index for insertion, allocating it
+    ///     first if the arena is sparse.
+    ///
+    /// # Arguments
+    /// * self: the arena to look in
+    /// * id: the node whose cluster to find
+    /// * cluster: the index of the cluster
+    ///
+    /// # Returns
+    /// * The id of the cluster
+    fn cluster_entry(&mut self, id: usize, cluster: K) -> usize {
+        if let Some(child) = self.cluster(id, cluster) {
+            return child;
+        }
+        let last = self.nodes[id].cluster_max(cluster);
+        let child = self.alloc(last);
+        match self.nodes[id].clusters {
+            Clusters::Sparse(ref mut children) => {
+                children.insert(cluster, child);
+            }
+            _ => panic!("Data structure appears corrupt"),
+        }
+        child
+    }
+
+    /// Returns the id of a node's aux tree in the aux arena, creating
+    ///     it first if the node is sparse and has none yet.
+    fn aux_entry(&mut self, id: usize) -> usize {
+        if let Some(aux) = self.nodes[id].aux {
+            return aux;
+        }
+        let cluster_last = self.nodes[id].cluster_last();
+        let aux = self.aux_arena().alloc(cluster_last);
+        self.nodes[id].aux = Some(aux);
+        aux
+    }
+
+    /// Records that a cluster has become empty, removing it from the
+    ///     aux tree and freeing it (and the aux tree) if the arena is
+    ///     sparse.
+    ///
+    /// # Arguments
+    /// * self: the arena to operate on
+    /// * id: the node the cluster belongs to
+    /// * cluster: the index of the now empty cluster
+    fn release_cluster(&mut self, id: usize, cluster: K) {
+        let aux = self.nodes[id].aux.expect("Data structure appears corrupt");
+        let aux_arena = self.aux_arena();
+        aux_arena.remove_in(aux, cluster);
+        if !self.sparse {
+            return;
+        }
+        let child = match self.nodes[id].clusters {
+            Clusters::Sparse(ref mut children) => {
+                let child = children.remove(&cluster);
+                if children.len() * 4 < children.capacity() {
+                    children.shrink_to_fit();
+                }
+                child
+            }
+            _ => None,
+        };
+        if let Some(child) = child {
+            self.release(child);
+        }
+        let aux_arena = self.aux_arena();
+        if aux_arena.nodes[aux].min.is_none() {
+            aux_arena.release(aux);
+            self.nodes[id].aux = None;
+        }
+    }
+
+    /// Returns the smallest populated cluster index of a node, None if
+    ///     all of its clusters are empty
+    fn first_cluster(&self, id: usize) -> Option<K> {
+        self.aux.as_ref()?.nodes[self.nodes[id].aux?].min
+    }
+
+    /// Returns the largest populated cluster index of a node, None if
+    ///     all of its clusters are empty
+    fn last_cluster(&self, id: usize) -> Option<K> {
+        self.aux.as_ref()?.nodes[self.nodes[id].aux?].max
+    }
+
+    /// Finds the next populated cluster of a node after a given one
+    fn next_cluster(&self, id: usize, cluster: K) -> Option<K> {
+        self.aux.as_ref()?.findnext_in(self.nodes[id].aux?, cluster)
+    }
+
+    /// Finds the previous populated cluster of a node before a given
+    ///     one
+    fn prev_cluster(&self, id: usize, cluster: K) -> Option<K> {
+        self.aux.as_ref()?.findprev_in(self.nodes[id].aux?, cluster)
+    }
+
+    /// Returns whether or not the given element is in the arena
+    ///
+    /// # Arguments
+    /// * self: the instance of the Arena
+    /// * value: the value for which to check membership
+    ///
+    /// # Returns
+    /// * Whether or not the value is contained in the arena
+    pub fn contains(&self, value: K) -> bool {
+        self.contains_in(ROOT, value)
+    }
+
+    fn contains_in(&self, id: usize, value: K) -> bool {
+        let node = &self.nodes[id];
+        match (node.min, node.max) {
+            (Some(min_val), Some(max_val)) => {
+                if value == min_val || value == max_val {
+                    true
+                } else if node.is_leaf() {
+                    node.leaf_contains(value)
+                } else {
+                    self.cluster(id, node.high(value))
+                        .is_some_and(|cluster| self.contains_in(cluster, node.low(value)))
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Searches the arena for the given value and returns the value if
+    ///     it is in the arena, None if not.
+    ///
+    /// # Arguments
+    /// * self: the instance of the Arena to operate on
+    /// * value: the value to search for in the arena
+    ///
+    /// # Returns
+    /// * The value being searched for or None if the value
+    ///   is not in the arena.
+    pub fn search(&self, value: K) -> Option<K> {
+        if self.contains(value) {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Gets a reference to the value stored with the given key
+    ///
+    /// # Arguments
+    /// * self: the instance of the Arena to operate on
+    /// * key: the key to look up
+    ///
+    /// # Returns
+    /// * The value stored with 'key' or None if the key is not
+    ///   in the arena
+    pub fn get(&self, key: K) -> Option<&V> {
+        let (mut id, mut key) = (ROOT, key);
+        loop {
+            let node = &self.nodes[id];
+            if node.is_leaf() {
+                if !node.leaf_contains(key) {
+                    return None;
+                }
+                return node.values.get(node.leaf_slot(key));
+            }
+            if key == node.min? {
+                return node.min_value.as_ref();
+            }
+            id = self.cluster(id, node.high(key))?;
+            key = node.low(key);
+        }
+    }
+
+    /// Gets a mutable reference to the value stored with the given key
+    ///
+    /// # Arguments
+    /// * self: the instance of the Arena to operate on
+    /// * key: the key to look up
+    ///
+    /// # Returns
+    /// * The value stored with 'key' or None if the key is not
+    ///   in the arena
+    pub fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        let (mut id, mut key) = (ROOT, key);
+        loop {
+            let node = &self.nodes[id];
+            if node.is_leaf() {
+                if !node.leaf_contains(key) {
+                    return None;
+                }
+                let slot = node.leaf_slot(key);
+                return self.nodes[id].values.get_mut(slot);
+            }
+            if key == node.min? {
+                return self.nodes[id].min_value.as_mut();
+            }
+            let (high, low) = (node.high(key), node.low(key));
+            id = self.cluster(id, high)?;
+            key = low;
+        }
+    }
+
+    /// Convenience function to handle making the recursive insert calls
+    ///     into the child nodes.
+    ///
+    /// # Arguments
+    /// * self: the instance of the Arena to operate on
+    /// * id: the node whose clusters to insert into
+    /// * key: the key to insert into the clusters
+    /// * value: the value to store with the key
+    ///
+    /// # Returns
+    /// * The value previously stored with 'key', if any
+    fn insert_into_tree(&mut self, id: usize, key: K, value: V) -> Option<V> {
+        let local_idx = self.nodes[id].high(key);
+        let pass_value = self.nodes[id].low(key);
+        let cluster_empty = self
+            .cluster(id, local_idx)
+            .is_none_or(|cluster| self.nodes[cluster].min.is_none());
+        if cluster_empty {
+            let aux = self.aux_entry(id);
+            self.aux_arena().insert_in(aux, local_idx, ());
+        }
+        let cluster = self.cluster_entry(id, local_idx);
+        let old = self.insert_in(cluster, pass_value, value);
+        if old.is_none() {
+            let node = &mut self.nodes[id];
+            node.len += 1;
+            let end = node.cluster_end();
+            node.counts
+                .update(&mut self.totals, local_idx, end, 1, true);
+        }
+        old
+    }
+
+    /// Insert a key into the arena, replacing the stored value if the
+    ///     key is already present.
+    ///
+    /// # Arguments
+    /// * self: the instance of the Arena to operate on
+    /// * key: the key to insert into the arena
+    /// * value: the value to store with the key
+    ///
+    /// # Returns
+    /// * The value previously stored with 'key', if any
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = self.insert_in(ROOT, key, value);
+        self.debug_validate();
+        old
+    }
+
+    fn insert_in(&mut self, id: usize, key: K, value: V) -> Option<V> {
+        let node = &mut self.nodes[id];
+        if node.is_leaf() {
+            return node.insert_into_leaf(key, value);
+        }
+        let min_value = match node.min {
+            Some(min_value) => min_value,
+            None => {
+                node.min = Some(key);
+                node.max = Some(key);
+                node.min_value = Some(value);
+                node.len = 1;
+                return None;
+            }
+        };
+        if key == min_value {
+            return node.min_value.replace(value);
+        }
+        let (pass_key, pass_value) = if key < min_value {
+            // The new key becomes the minimum and the old minimum
+            // moves down into the clusters instead
+            node.min = Some(key);
+            let old_value = node
+                .min_value
+                .replace(value)
+                .expect("Data structure appears corrupt");
+            (min_value, old_value)
+        } else {
+            (key, value)
+        };
+        let old = self.insert_into_tree(id, pass_key, pass_value);
+        let node = &mut self.nodes[id];
+        if node.max < Some(pass_key) {
+            node.max = Some(pass_key);
+        }
+        old
+    }
+
+    /// Convenience function to manage making recursive delete calls
+    ///     into the child nodes
+    ///
+    /// # Arguments
+    /// * self: the instance of the Arena to operate on
+    /// * id: the node whose clusters to delete from
+    /// * key: the key to delete from the child nodes
+    ///
+    /// # Returns
+    /// * The value that was stored with 'key', None if the key was
+    ///   not in the node
+    fn delete_from_tree(&mut self, id: usize, key: K) -> Option<V> {
+        let local_idx = self.nodes[id].high(key);
+        let pass_value = self.nodes[id].low(key);
+        let cluster = self.cluster(id, local_idx)?;
+        let removed = self.remove_in(cluster, pass_value)?;
+        if self.nodes[cluster].min.is_none() {
+            self.release_cluster(id, local_idx);
+        }
+        let node = &mut self.nodes[id];
+        node.len -= 1;
+        let end = node.cluster_end();
+        node.counts
+            .update(&mut self.totals, local_idx, end, 1, false);
+        Some(removed)
+    }
+
+    /// Removes a key from the arena, returning its value
+    ///
+    /// # Arguments
+    /// * self: the instance of the Arena to operate on
+    /// * key: the key to delete from the arena
+    ///
+    /// # Returns
+    /// * The value that was stored with 'key', None if the key was
+    ///   not in the arena
+    pub fn remove(&mut self, key: K) -> Option<V> {
+        let removed = self.remove_in(ROOT, key);
+        self.reclaim();
+        self.debug_validate();
+        removed
+    }
+
+    fn remove_in(&mut self, id: usize, key: K) -> Option<V> {
+        let node = &mut self.nodes[id];
+        if node.is_leaf() {
+            return node.remove_from_leaf(key);
+        }
+        let min_value = node.min?;
+        let max_value = node.max?;
+        if min_value == max_value {
+            if key != min_value {
+                return None;
+            }
+            node.min = None;
+            node.max = None;
+            node.len = 0;
+            return node.min_value.take();
+        }
+        let removed = if key == min_value {
+            // Pull the smallest element out of the clusters to become
+            // the new minimum
+            let first_cluster = self
+                .first_cluster(id)
+                .expect("Data structure appears corrupt");
+            let offset = self
+                .cluster(id, first_cluster)
+                .and_then(|cluster| self.nodes[cluster].min)
+                .expect("Data structure appears corrupt");
+            let new_min = self.nodes[id].index(first_cluster, offset);
+            let new_min_value = self.delete_from_tree(id, new_min);
+            let node = &mut self.nodes[id];
+            node.min = Some(new_min);
+            mem::replace(&mut node.min_value, new_min_value)
+                .expect("Data structure appears corrupt")
+        } else {
+            self.delete_from_tree(id, key)?
+        };
+        if key == max_value {
+            let new_max = match self.last_cluster(id) {
+                Some(last_cluster) => {
+                    let offset = self
+                        .cluster(id, last_cluster)
+                        .and_then(|cluster| self.nodes[cluster].max)
+                        .expect("Data structure appears corrupt");
+                    Some(self.nodes[id].index(last_cluster, offset))
+                }
+                None => self.nodes[id].min,
+            };
+            self.nodes[id].max = new_max;
+        }
+        Some(removed)
+    }
+
+    /// Removes the smallest element of the arena and returns it with
+    ///     its value
+    ///
+    /// The minimum is cached in the node, so this is a single descent
+    ///     that pulls the next smallest element up from the first
+    ///     cluster, without looking the minimum up first.
+    ///
+    /// # Arguments
+    /// * self: the instance of the Arena to operate on
+    ///
+    /// # Returns
+    /// * The removed minimum and its value, None if the arena is empty
+    pub fn pop_min(&mut self) -> Option<(K, V)> {
+        let popped = self.pop_min_in(ROOT);
+        self.reclaim();
+        self.debug_validate();
+        popped
+    }
+
+    fn pop_min_in(&mut self, id: usize) -> Option<(K, V)> {
+        let node = &mut self.nodes[id];
+        let min_value = node.min?;
+        if node.is_leaf() {
+            let value = node.remove_from_leaf(min_value)?;
+            return Some((min_value, value));
+        }
+        if node.max == Some(min_value) {
+            node.min = None;
+            node.max = None;
+            node.len = 0;
+            return Some((min_value, node.min_value.take()?));
+        }
+        // The new minimum is the smallest element of the first cluster
+        let first_cluster = self
+            .first_cluster(id)
+            .expect("Data structure appears corrupt");
+        let cluster = self
+            .cluster(id, first_cluster)
+            .expect("Data structure appears corrupt");
+        let (offset, new_min_value) = self
+            .pop_min_in(cluster)
+            .expect("Data structure appears corrupt");
+        if self.nodes[cluster].min.is_none() {
+            self.release_cluster(id, first_cluster);
+        }
+        let node = &mut self.nodes[id];
+        node.len -= 1;
+        let end = node.cluster_end();
+        node.counts
+            .update(&mut self.totals, first_cluster, end, 1, false);
+        node.min = Some(node.index(first_cluster, offset));
+        let value = node.min_value.replace(new_min_value)?;
+        Some((min_value, value))
+    }
+
+    /// Removes the largest element of the arena and returns it with
+    ///     its value
+    ///
+    /// The maximum of a node is always stored in its last cluster, so
+    ///     this is a single descent through the last clusters.
+    ///
+    /// # Arguments
+    /// * self: the instance of the Arena to operate on
+    ///
+    /// # Returns
+    /// * The removed maximum and its value, None if the arena is empty
+    pub fn pop_max(&mut self) -> Option<(K, V)> {
+        let popped = self.pop_max_in(ROOT);
+        self.reclaim();
+        self.debug_validate();
+        popped
+    }
+
+    fn pop_max_in(&mut self, id: usize) -> Option<(K, V)> {
+        let node = &mut self.nodes[id];
+        let max_value = node.max?;
+        if node.is_leaf() {
+            let value = node.remove_from_leaf(max_value)?;
+            return Some((max_value, value));
+        }
+        if node.min == Some(max_value) {
+            node.min = None;
+            node.max = None;
+            node.len = 0;
+            return Some((max_value, node.min_value.take()?));
+        }
+        let last_cluster = self
+            .last_cluster(id)
+            .expect("Data structure appears corrupt");
+        let cluster = self
+            .cluster(id, last_cluster)
+            .expect("Data structure appears corrupt");
+        let (_, value) = self
+            .pop_max_in(cluster)
+            .expect("Data structure appears corrupt");
+        if self.nodes[cluster].min.is_none() {
+            self.release_cluster(id, last_cluster);
+        }
+        // The next largest element is the maximum of what is now the
+        // last cluster, or the minimum once the clusters are empty
+        let new_max = match self.last_cluster(id) {
+            Some(last_cluster) => {
+                let offset = self
+                    .cluster(id, last_cluster)
+                    .and_then(|cluster| self.nodes[cluster].max)
+                    .expect("Data structure appears corrupt");
+                Some(self.nodes[id].index(last_cluster, offset))
+            }
+            None => self.nodes[id].min,
+        };
+        let node = &mut self.nodes[id];
+        node.len -= 1;
+        let end = node.cluster_end();
+        node.counts
+            .update(&mut self.totals, last_cluster, end, 1, false);
+        node.max = new_max;
+        Some((max_value, value))
+    }
+
+    /// Fills an empty arena from entries in strictly ascending key
+    ///     order, building every cluster and aux tree bottom-up in a
+    ///     single pass over each level instead of inserting the keys
+    ///     one at a time.
+    ///
+    /// # Arguments
+    /// * self: the empty arena to fill
+    /// * entries: the keys and their values, strictly ascending and
+    ///   all inside the universe of the arena
+    pub fn fill_sorted<I: Iterator<Item = (K, V)>>(&mut self, entries: I) {
+        self.fill_sorted_in(ROOT, entries);
+        self.debug_validate();
+    }
+
+    fn fill_sorted_in<I: Iterator<Item = (K, V)>>(&mut self, id: usize, mut entries: I) {
+        let node = &mut self.nodes[id];
+        if node.is_leaf() {
+            for (key, value) in entries {
+                node.bits |= Node::<K, V>::leaf_bit(key);
+                node.values.push(value);
+            }
+            node.len = node.values.len();
+            node.update_leaf_bounds();
+            return;
+        }
+        let (min_key, min_value) = match entries.next() {
+            Some(entry) => entry,
+            None => return,
+        };
+        node.min = Some(min_key);
+        node.max = Some(min_key);
+        node.min_value = Some(min_value);
+        node.len = 1;
+        // Everything after the minimum goes into the clusters, which
+        // are filled one at a time as the keys move past them
+        let mut populated: Vec<(K, ())> = Vec::new();
+        let mut group: Vec<(K, V)> = Vec::new();
+        for (key, value) in entries {
+            let local_idx = self.nodes[id].high(key);
+            if populated
+                .last()
+                .is_none_or(|&(index, _)| index != local_idx)
+            {
+                if let Some(&(index, _)) = populated.last() {
+                    self.fill_cluster(id, index, &mut group);
+                }
+                populated.push((local_idx, ()));
+            }
+            let node = &mut self.nodes[id];
+            group.push((node.low(key), value));
+            node.max = Some(key);
+            node.len += 1;
+        }
+        if let Some(&(index, _)) = populated.last() {
+            self.fill_cluster(id, index, &mut group);
+            let aux = self.aux_entry(id);
+            self.aux_arena().fill_sorted_in(aux, populated.into_iter());
+        }
+    }
+
+    /// Fills one cluster of a node during fill_sorted, emptying the
+    ///     group of entries collected for it, and counts them
+    fn fill_cluster(&mut self, id: usize, index: K, group: &mut Vec<(K, V)>) {
+        let amount = group.len();
+        let cluster = self.cluster_entry(id, index);
+        self.fill_sorted_in(cluster, group.drain(..));
+        let node = &mut self.nodes[id];
+        let end = node.cluster_end();
+        node.counts
+            .update(&mut self.totals, index, end, amount, true);
+    }
+
+    /// Gets the number of elements stored in the arena
+    ///
+    /// # Arguments
+    /// * self: the instance of Arena to operate on
+    ///
+    /// # Returns
+    /// * The number of elements, including min
+    pub fn len(&self) -> usize {
+        self.nodes[ROOT].len
+    }
+
+    /// Counts the elements of the arena that are smaller than a value
+    ///
+    /// Each level of the recursion totals the clusters before the
+    ///     value from the node's counts, in one step per bit of the
+    ///     cluster index, and descends into the value's own cluster.
+    ///
+    /// # Arguments
+    /// * self: the instance of Arena to operate on
+    /// * value: the value to rank
+    ///
+    /// # Returns
+    /// * The number of elements smaller than 'value'
+    pub fn rank(&self, value: K) -> usize {
+        self.rank_in(ROOT, value)
+    }
+
+    fn rank_in(&self, id: usize, value: K) -> usize {
+        let node = &self.nodes[id];
+        let (min_value, max_value) = match (node.min, node.max) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            _ => return 0,
+        };
+        if value <= min_value {
+            return 0;
+        }
+        if value > max_value {
+            return node.len;
+        }
+        if node.is_leaf() {
+            return node.leaf_slot(value);
+        }
+        let local_idx = node.high(value);
+        let in_cluster = self
+            .cluster(id, local_idx)
+            .map_or(0, |cluster| self.rank_in(cluster, node.low(value)));
+        1 + node.counts.before(&self.totals, local_idx) + in_cluster
+    }
+
+    /// Finds the element with a given number of smaller elements
+    ///
+    /// # Arguments
+    /// * self: the instance of Arena to operate on
+    /// * rank: the 0-based position of the element in sorted order
+    ///
+    /// # Returns
+    /// * The element at position 'rank', None if rank >= len
+    pub fn select(&self, rank: usize) -> Option<K> {
+        self.select_in(ROOT, rank)
+    }
+
+    fn select_in(&self, id: usize, rank: usize) -> Option<K> {
+        let node = &self.nodes[id];
+        if rank >= node.len {
+            return None;
+        }
+        if node.is_leaf() {
+            // Drop the lowest set bits until the one wanted is lowest
+            let mut bits = node.bits;
+            for _ in 0..rank {
+                bits &= bits - 1;
+            }
+            return Some(K::from_usize(bits.trailing_zeros() as usize));
+        }
+        if rank == 0 {
+            return node.min;
+        }
+        // The minimum is not stored in any cluster
+        let (index, remaining) = node.counts.find(&self.totals, rank - 1, node.cluster_end());
+        let cluster = self.cluster(id, index)?;
+        Some(node.index(index, self.select_in(cluster, remaining)?))
+    }
+
+    /// Gets the minimum of the currently stored elements
+    ///
+    /// # Arguments
+    /// * self: the instance of Arena to operate on
+    ///
+    /// # Returns
+    /// * The minimum element currently stored in the arena
+    pub fn minimum(&self) -> Option<K> {
+        self.nodes[ROOT].min
+    }
+
+    /// Gets the maximum of the currently stored elements
+    ///
+    /// # Arguments
+    /// * self: &Self - the instance of Arena to operate on.
+    ///
+    /// # Returns
+    /// * The maximum element currently stored in the arena
+    pub fn maximum(&self) -> Option<K> {
+        self.nodes[ROOT].max
+    }
+
+    /// Finds the next consecutive element currently in the arena
+    ///
+    /// # Arguments
+    /// * self: the instance of Arena to operate on.
+    /// * value: the value to find the successor of.
+    ///
+    /// # Returns
+    /// * The successor of 'value' or None if not found
+    pub fn findnext(&self, value: K) -> Option<K> {
+        self.findnext_in(ROOT, value)
+    }
+
+    fn findnext_in(&self, id: usize, value: K) -> Option<K> {
+        let node = &self.nodes[id];
+        let min_value = node.min?;
+        let max_value = node.max?;
+        if value < min_value {
+            return node.min;
+        }
+        if value >= max_value {
+            return None;
+        }
+        if node.is_leaf() {
+            // min <= value < max, so value is below the top bit
+            let above = node.bits & !((Node::<K, V>::leaf_bit(value) << 1) - 1);
+            return Some(K::from_usize(above.trailing_zeros() as usize));
+        }
+        let local_idx = node.high(value);
+        let pass_value = node.low(value);
+        if let Some(cluster) = self.cluster(id, local_idx) {
+            if self.nodes[cluster].max.is_some_and(|max| pass_value < max) {
+                let offset = self.findnext_in(cluster, pass_value)?;
+                return Some(node.index(local_idx, offset));
+            }
+        }
+        let next_cluster = self.next_cluster(id, local_idx)?;
+        let offset = self.nodes[self.cluster(id, next_cluster)?].min?;
+        Some(node.index(next_cluster, offset))
+    }
+
+    /// Finds the immediate previous element currently in the arena
+    ///
+    /// # Arguments
+    /// * self: the instance of Arena to operate on
+    /// * value: the value to find the predecessor of
+    ///
+    /// # Returns
+    /// * The predecessor of 'value' or None if not found
+    pub fn findprev(&self, value: K) -> Option<K> {
+        self.findprev_in(ROOT, value)
+    }
+
+    fn findprev_in(&self, id: usize, value: K) -> Option<K> {
+        let node = &self.nodes[id];
+        let min_value = node.min?;
+        let max_value = node.max?;
+        if value > max_value {
+            return node.max;
+        }
+        if value <= min_value {
+            return None;
+        }
+        if node.is_leaf() {
+            // min < value <= max, so value is a valid bit position
+            let below = node.bits & (Node::<K, V>::leaf_bit(value) - 1);
+            return Some(K::from_usize(63 - below.leading_zeros() as usize));
+        }
+        let local_idx = node.high(value);
+        let pass_value = node.low(value);
+        if let Some(cluster) = self.cluster(id, local_idx) {
+            if self.nodes[cluster].min.is_some_and(|min| pass_value > min) {
+                let offset = self.findprev_in(cluster, pass_value)?;
+                return Some(node.index(local_idx, offset));
+            }
+        }
+        // The minimum is not stored in any cluster, so it is the
+        // predecessor when no earlier cluster is populated
+        match self.prev_cluster(id, local_idx) {
+            Some(prev_cluster) => {
+                let offset = self.nodes[self.cluster(id, prev_cluster)?].max?;
+                Some(node.index(prev_cluster, offset))
+            }
+            None => node.min,
+        }
+    }
+
+    /// Finds the smallest element that is at least a value, in a
+    ///     single descent
+    ///
+    /// # Arguments
+    /// * self: the instance of Arena to operate on
+    /// * value: the lower bound to search from
+    ///
+    /// # Returns
+    /// * 'value' itself if it is present, otherwise its successor,
+    ///   None if every element is smaller
+    pub fn ceil(&self, value: K) -> Option<K> {
+        self.ceil_in(ROOT, value)
+    }
+
+    fn ceil_in(&self, id: usize, value: K) -> Option<K> {
+        let node = &self.nodes[id];
+        let min_value = node.min?;
+        let max_value = node.max?;
+        if value <= min_value {
+            return node.min;
+        }
+        if value >= max_value {
+            return if value == max_value { node.max } else { None };
+        }
+        if node.is_leaf() {
+            // min < value < max, so value is a valid bit position
+            let at_or_above = node.bits & !(Node::<K, V>::leaf_bit(value) - 1);
+            return Some(K::from_usize(at_or_above.trailing_zeros() as usize));
+        }
+        let local_idx = node.high(value);
+        let pass_value = node.low(value);
+        if let Some(cluster) = self.cluster(id, local_idx) {
+            if self.nodes[cluster].max.is_some_and(|max| pass_value <= max) {
+                let offset = self.ceil_in(cluster, pass_value)?;
+                return Some(node.index(local_idx, offset));
+            }
+        }
+        let next_cluster = self.next_cluster(id, local_idx)?;
+        let offset = self.nodes[self.cluster(id, next_cluster)?].min?;
+        Some(node.index(next_cluster, offset))
+    }
+
+    /// Finds the largest element that is at most a value, in a
+    ///     single descent
+    ///
+    /// # Arguments
+    /// * self: the instance of Arena to operate on
+    /// * value: the upper bound to search from
+    ///
+    /// # Returns
+    /// * 'value' itself if it is present, otherwise its predecessor,
+    ///   None if every element is larger
+    pub fn floor(&self, value: K) -> Option<K> {
+        self.floor_in(ROOT, value)
+    }
+
+    fn floor_in(&self, id: usize, value: K) -> Option<K> {
+        let node = &self.nodes[id];
+        let min_value = node.min?;
+        let max_value = node.max?;
+        if value >= max_value {
+            return node.max;
+        }
+        if value <= min_value {
+            return if value == min_value { node.min } else { None };
+        }
+        if node.is_leaf() {
+            // min < value < max, so value is below the top bit
+            let at_or_below = node.bits & ((Node::<K, V>::leaf_bit(value) << 1) - 1);
+            return Some(K::from_usize(63 - at_or_below.leading_zeros() as usize));
+        }
+        let local_idx = node.high(value);
+        let pass_value = node.low(value);
+        if let Some(cluster) = self.cluster(id, local_idx) {
+            if self.nodes[cluster].min.is_some_and(|min| pass_value >= min) {
+                let offset = self.floor_in(cluster, pass_value)?;
+                return Some(node.index(local_idx, offset));
+            }
+        }
+        // The minimum is not stored in any cluster, so it is the
+        // floor when no earlier cluster is populated
+        match self.prev_cluster(id, local_idx) {
+            Some(prev_cluster) => {
+                let offset = self.nodes[self.cluster(id, prev_cluster)?].max?;
+                Some(node.index(prev_cluster, offset))
+            }
+            None => node.min,
+        }
+    }
+
+    /// Checks every structural invariant of the arena, walking each
+    ///     node and aux tree once.
+    ///
+    /// # Arguments
+    /// * self: the instance of Arena to check
+    ///
+    /// # Returns
+    /// * Ok if the arena is consistent, otherwise the first
+    ///   InvariantViolation found
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        self.validate_in(ROOT)
+    }
+
+    /// Validates the arena after a mutation when the debug-invariants
+    ///     feature is enabled, and does nothing otherwise.
+    ///
+    /// # Panics
+    /// * If the feature is enabled and the arena is inconsistent
+    fn debug_validate(&self) {
+        if cfg!(feature = "debug-invariants") {
+            if let Err(err) = self.validate() {
+                panic!("Data structure appears corrupt: {}", err);
+            }
+        }
+    }
+
+    fn validate_in(&self, id: usize) -> Result<(), InvariantViolation> {
+        let node = &self.nodes[id];
+        let (min_value, max_value) = match (node.min, node.max) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            (None, None) => (K::ZERO, K::ZERO),
+            _ => return Err(InvariantViolation::BoundNotPresent { node: id }),
+        };
+        if min_value > max_value {
+            return Err(InvariantViolation::MinAboveMax { node: id });
+        }
+        if node.min.is_some() && max_value > node.last {
+            return Err(InvariantViolation::KeyOutsideUniverse { node: id });
+        }
+        if node.is_leaf() {
+            self.validate_leaf(id)
+        } else {
+            self.validate_clusters(id)
+        }
+    }
+
+    /// Checks a leaf's bitmap against its universe, bounds, count and
+    ///     values.
+    fn validate_leaf(&self, id: usize) -> Result<(), InvariantViolation> {
+        let node = &self.nodes[id];
+        let last = node.last.to_usize();
+        if last < LEAF_SIZE - 1 && node.bits >> (last + 1) != 0 {
+            return Err(InvariantViolation::KeyOutsideUniverse { node: id });
+        }
+        let stored = node.bits.count_ones() as usize;
+        if node.len != stored {
+            return Err(InvariantViolation::CountMismatch {
+                node: id,
+                expected: stored,
+                found: node.len,
+            });
+        }
+        if node.values.len() != stored || node.min_value.is_some() {
+            return Err(InvariantViolation::ValueMismatch { node: id });
+        }
+        let (min_value, max_value) = match (node.min, node.max) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            _ if node.bits == 0 => return Ok(()),
+            _ => return Err(InvariantViolation::BoundNotPresent { node: id }),
+        };
+        if !node.leaf_contains(min_value) || !node.leaf_contains(max_value) {
+            return Err(InvariantViolation::BoundNotPresent { node: id });
+        }
+        if node.bits.trailing_zeros() as usize != min_value.to_usize() {
+            return Err(InvariantViolation::MinNotSmallest { node: id });
+        }
+        if 63 - node.bits.leading_zeros() as usize != max_value.to_usize() {
+            return Err(InvariantViolation::MaxNotLargest { node: id });
+        }
+        Ok(())
+    }
+
+    /// Checks the clusters of a node against its aux tree, count and
+    ///     bounds, recursing into each cluster and the aux tree.
+    fn validate_clusters(&self, id: usize) -> Result<(), InvariantViolation> {
+        let node = &self.nodes[id];
+        if node.min.is_some() != node.min_value.is_some() || !node.values.is_empty() {
+            return Err(InvariantViolation::ValueMismatch { node: id });
+        }
+        let aux_arena = self.aux.as_ref();
+        if let (Some(aux_arena), Some(aux)) = (aux_arena, node.aux) {
+            let expected = node.cluster_last();
+            let found = aux_arena.nodes[aux].last;
+            if found != expected {
+                return Err(InvariantViolation::UniverseMismatch {
+                    node: id,
+                    expected: universe_size(Some(expected)),
+                    found: universe_size(Some(found)),
+                });
+            }
+            aux_arena.validate_in(aux)?;
+        }
+        let in_aux = |cluster: K| {
+            aux_arena
+                .and_then(|aux_arena| Some(aux_arena.contains_in(node.aux?, cluster)))
+                .unwrap_or(false)
+        };
+        let allocated: Vec<(K, usize)> = match node.clusters {
+            Clusters::Leaf => Vec::new(),
+            Clusters::Dense(first) => (0..node.cluster_count())
+                .map(|cluster| (K::from_usize(cluster), first + cluster))
+                .collect(),
+            Clusters::Sparse(ref children) => children
+                .iter()
+                .map(|(&cluster, &child)| (cluster, child))
+                .collect(),
+        };
+        let mut clustered = 0;
+        let mut lens = Vec::with_capacity(allocated.len());
+        for (cluster, child) in allocated {
+            let expected = node.cluster_max(cluster);
+            let found = self.nodes[child].last;
+            if found != expected {
+                return Err(InvariantViolation::UniverseMismatch {
+                    node: child,
+                    expected: universe_size(Some(expected)),
+                    found: universe_size(Some(found)),
+                });
+            }
+            self.validate_in(child)?;
+            let populated = self.nodes[child].min.is_some();
+            if populated && !in_aux(cluster) {
+                return Err(InvariantViolation::ClusterMissingFromAux {
+                    node: id,
+                    cluster: cluster.to_usize(),
+                });
+            }
+            if !populated && self.sparse {
+                return Err(InvariantViolation::EmptyClusterKept {
+                    node: id,
+                    cluster: cluster.to_usize(),
+                });
+            }
+            clustered += self.nodes[child].len;
+            lens.push((cluster, self.nodes[child].len));
+        }
+        if self.counted && !node.counts.matches(&self.totals, &lens, node.cluster_end()) {
+            return Err(InvariantViolation::ClusterCountMismatch { node: id });
+        }
+        // Every index in the aux tree must name a populated cluster,
+        // which also catches indices of sparse clusters never allocated
+        let mut current = self.first_cluster(id);
+        while let Some(index) = current {
+            let populated = self
+                .cluster(id, index)
+                .is_some_and(|child| self.nodes[child].min.is_some());
+            if !populated {
+                return Err(InvariantViolation::EmptyClusterInAux {
+                    node: id,
+                    cluster: index.to_usize(),
+                });
+            }
+            current = self.next_cluster(id, index);
+        }
+        let expected = clustered + if node.min.is_some() { 1 } else { 0 };
+        if node.len != expected {
+            return Err(InvariantViolation::CountMismatch {
+                node: id,
+                expected,
+                found: node.len,
+            });
+        }
+        let (min_value, max_value) = match (node.min, node.max) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            _ => return Ok(()),
+        };
+        let (first, last) = match (self.first_cluster(id), self.last_cluster(id)) {
+            (Some(first), Some(last)) => (first, last),
+            _ if min_value == max_value => return Ok(()),
+            _ => return Err(InvariantViolation::BoundNotPresent { node: id }),
+        };
+        let bound = |index: K, pick: fn(&Node<K, V>) -> Option<K>| {
+            self.cluster(id, index)
+                .and_then(|child| pick(&self.nodes[child]))
+                .map(|offset| node.index(index, offset))
+                .expect("Data structure appears corrupt")
+        };
+        if bound(first, |child| child.min) <= min_value {
+            return Err(InvariantViolation::MinNotSmallest { node: id });
+        }
+        let largest = bound(last, |child| child.max);
+        if largest != max_value {
+            let stored = self
+                .cluster(id, node.high(max_value))
+                .is_some_and(|child| self.contains_in(child, node.low(max_value)));
+            return Err(if stored {
+                InvariantViolation::MaxNotLargest { node: id }
+            } else {
+                InvariantViolation::BoundNotPresent { node: id }
+            });
+        }
+        Ok(())
+    }
+
+    /// Estimates the heap memory held by the arena and its aux arenas.
+    ///
+    /// Buffers are counted by capacity, and the cluster maps of sparse
+    ///     nodes by the number of entries they have room for plus a
+    ///     control byte each, which is how the standard HashMap lays
+    ///     them out.  Heap memory owned by the values themselves is
+    ///     not followed.
+    ///
+    /// # Arguments
+    /// * self: the instance of Arena to measure
+    ///
+    /// # Returns
+    /// * The number of heap bytes in use
+    pub fn heap_size_bytes(&self) -> usize {
+        self.children_bytes() + self.aux_bytes()
+    }
+
+    /// Returns the heap bytes of this arena's own nodes, leaving out
+    ///     the aux arenas
+    fn children_bytes(&self) -> usize {
+        let mut bytes = self.nodes.capacity() * mem::size_of::<Node<K, V>>()
+            + (self.free.capacity() + self.totals.capacity()) * mem::size_of::<usize>();
+        for node in &self.nodes {
+            bytes += node.values.capacity() * mem::size_of::<V>();
+            bytes += node.counts.heap_size_bytes();
+            if let Clusters::Sparse(ref children) = node.clusters {
+                bytes += children.capacity() * (mem::size_of::<(K, usize)>() + 1);
+            }
+        }
+        bytes
+    }
+
+    /// Returns the heap bytes of the aux arenas, including the box
+    ///     holding the first of them
+    fn aux_bytes(&self) -> usize {
+        self.aux.as_ref().map_or(0, |aux| {
+            mem::size_of::<Arena<K, ()>>() + aux.heap_size_bytes()
+        })
+    }
+
+    /// Returns the number of nodes in use in this arena and its aux
+    ///     arenas, leaving out freed nodes waiting to be reused
+    fn node_count(&self) -> usize {
+        let aux_nodes = self.aux.as_ref().map_or(0, |aux| aux.node_count());
+        self.nodes.len() - self.free.len() + aux_nodes
+    }
+
+    /// Reports how the arena's nodes are spread over the levels of the
+    ///     recursion and how much heap memory they use, walking every
+    ///     node once.
+    ///
+    /// # Arguments
+    /// * self: the instance of Arena to measure
+    ///
+    /// # Returns
+    /// * The statistics of the arena
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats::default();
+        let mut level = vec![ROOT];
+        while !level.is_empty() {
+            let mut level_stats = LevelStats::default();
+            let mut next = Vec::new();
+            for &id in &level {
+                let node = &self.nodes[id];
+                level_stats.universe =
+                    cmp::max(level_stats.universe, universe_size(Some(node.last)));
+                level_stats.nodes += 1;
+                if node.min.is_some() {
+                    level_stats.populated += 1;
+                }
+                match node.clusters {
+                    Clusters::Leaf => level_stats.leaves += 1,
+                    Clusters::Dense(first) => next.extend(first..first + node.cluster_count()),
+                    Clusters::Sparse(ref children) => next.extend(children.values().cloned()),
+                }
+            }
+            stats.levels.push(level_stats);
+            level = next;
+        }
+        for (depth, level_stats) in stats.levels.iter().enumerate() {
+            stats.leaves += level_stats.leaves;
+            if depth > 0 {
+                stats.allocated_clusters += level_stats.nodes;
+                stats.populated_clusters += level_stats.populated;
+            }
+        }
+        stats.aux_nodes = self.aux.as_ref().map_or(0, |aux| aux.node_count());
+        stats.children_bytes = self.children_bytes();
+        stats.aux_bytes = self.aux_bytes();
+        stats
+    }
+}
+
+impl<K: VEBKey, V: PartialEq> PartialEq for Arena<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.sparse != other.sparse
+            || self.nodes[ROOT].last != other.nodes[ROOT].last
+            || self.len() != other.len()
+        {
+            return false;
+        }
+        let mut current = self.minimum();
+        if current != other.minimum() {
+            return false;
+        }
+        while let Some(key) = current {
+            if self.get(key) != other.get(key) {
+                return false;
+            }
+            current = self.findnext(key);
+            if current != other.findnext(key) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl<K: VEBKey, V: Eq> Eq for Arena<K, V> {}
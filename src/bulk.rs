@@ -0,0 +1,120 @@
+use std::iter::FromIterator;
+
+use error::check_key;
+use key::{max_key_for, VEBKey};
+use VEBTree;
+
+/// Collects keys into strictly ascending order, sorting and
+///     deduplicating them only if they did not already arrive that way
+pub(crate) fn sorted_keys<K: VEBKey, I: IntoIterator<Item = K>>(iter: I) -> Vec<K> {
+    let mut keys: Vec<K> = iter.into_iter().collect();
+    if keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+        keys.sort_unstable();
+        keys.dedup();
+    }
+    keys
+}
+
+impl<K: VEBKey> VEBTree<K> {
+    /// Fills an empty tree from keys that are already strictly
+    ///     ascending
+    ///
+    /// # Panics
+    /// * If any of the keys is outside of the universe
+    fn fill_sorted(&mut self, keys: Vec<K>) {
+        if let Some(&last) = keys.last() {
+            if let Err(err) = check_key(last, self.max_key) {
+                panic!("{}", err);
+            }
+        }
+        self.arena.fill_sorted(keys.into_iter().map(|key| (key, ())));
+    }
+
+    /// Builds a tree of the given layout from keys that are already
+    ///     strictly ascending
+    ///
+    /// # Panics
+    /// * If any of the keys is outside of the universe
+    pub(crate) fn from_sorted_keys(max_key: Option<K>, sparse: bool, keys: Vec<K>) -> Self {
+        let mut tree = VEBTree::with_layout(max_key, sparse);
+        tree.fill_sorted(keys);
+        tree
+    }
+
+    /// Creates a new VEBTree holding the given keys, building it
+    ///     bottom-up instead of inserting them one at a time.
+    ///
+    /// Sorted input is placed directly in one pass over each level of
+    ///     the recursion.  Unsorted input, or input with duplicates,
+    ///     is sorted and deduplicated first.
+    ///
+    /// # Arguments
+    /// * universe: the maximum capacity of the tree, as for new
+    /// * iter: the keys to store, ideally in ascending order
+    ///
+    /// # Returns
+    /// * A tree of the given universe holding every key of iter
+    ///
+    /// # Panics
+    /// * If universe is larger than the number of values the key
+    ///   type can represent
+    /// * If any of the keys is outside of the universe
+    pub fn from_sorted_iter<I: IntoIterator<Item = K>>(universe: usize, iter: I) -> Self {
+        Self::from_sorted_keys(max_key_for(universe), false, sorted_keys(iter))
+    }
+
+    /// Creates a new sparse VEBTree holding the given keys, building
+    ///     it bottom-up instead of inserting them one at a time.
+    ///
+    /// # Arguments
+    /// * universe: the maximum capacity of the tree, as for new_sparse
+    /// * iter: the keys to store, ideally in ascending order
+    ///
+    /// # Returns
+    /// * A sparse tree of the given universe holding every key of
+    ///   iter
+    ///
+    /// # Panics
+    /// * If universe is larger than the number of values the key
+    ///   type can represent
+    /// * If any of the keys is outside of the universe
+    pub fn from_sorted_iter_sparse<I: IntoIterator<Item = K>>(universe: usize, iter: I) -> Self {
+        Self::from_sorted_keys(max_key_for(universe), true, sorted_keys(iter))
+    }
+}
+
+/// Collects keys into a sparse tree over every value of the key type,
+///     as with_max_key_sparse(K::MAX), built bottom-up as in
+///     from_sorted_iter.  Like a collected BTreeSet, the tree can take
+///     any key afterwards, and being sparse its memory grows with the
+///     keys rather than the universe.  Use from_sorted_iter or
+///     from_sorted_iter_sparse to choose the universe and layout
+///     instead.
+impl<K: VEBKey> FromIterator<K> for VEBTree<K> {
+    fn from_iter<I: IntoIterator<Item = K>>(iter: I) -> Self {
+        Self::from_sorted_keys(Some(K::MAX), true, sorted_keys(iter))
+    }
+}
+
+/// Inserts every key into the tree, keeping its universe.  An empty
+///     tree is filled bottom-up as in from_sorted_iter.
+///
+/// # Panics
+/// * If any of the keys is outside of the universe
+impl<K: VEBKey> Extend<K> for VEBTree<K> {
+    fn extend<I: IntoIterator<Item = K>>(&mut self, iter: I) {
+        if self.is_empty() {
+            self.fill_sorted(sorted_keys(iter));
+        } else {
+            for key in iter {
+                self.insert(key);
+            }
+        }
+    }
+}
+
+impl<'a, K: VEBKey> Extend<&'a K> for VEBTree<K> {
+    fn extend<I: IntoIterator<Item = &'a K>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().cloned());
+    }
+}
@@ -0,0 +1,232 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use key::VEBKey;
+
+/// Errors reported by the checked (try_) operations on VEBTree
+///
+/// # Variants
+/// * OutOfRange: the key is not smaller than the universe of the
+///   tree, so it can never be stored in it.  The universe is given
+///   in the key type, which always has room for it: a tree that
+///   covers every value of K has no keys out of range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VebError<K> {
+    OutOfRange { key: K, universe: K },
+}
+
+impl<K: VEBKey> fmt::Display for VebError<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VebError::OutOfRange { key, universe } => write!(
+                f,
+                "key {:?} is outside of the universe of {:?}",
+                key, universe
+            ),
+        }
+    }
+}
+
+impl<K: VEBKey> Error for VebError<K> {}
+
+/// Checks that a key can be stored in a universe, comparing in the key
+///     type so that no universe is too large to check against
+///
+/// # Arguments
+/// * key: the key to check
+/// * max_key: the largest key the structure can hold, None if it
+///   holds none
+///
+/// # Returns
+/// * Ok if key <= max_key, VebError::OutOfRange otherwise
+pub(crate) fn check_key<K: VEBKey>(key: K, max_key: Option<K>) -> Result<(), VebError<K>> {
+    match max_key {
+        Some(max_key) if key <= max_key => Ok(()),
+        // key > max_key, so max_key + 1 cannot overflow
+        _ => Err(VebError::OutOfRange {
+            key,
+            universe: max_key.map_or(K::ZERO, |max_key| max_key + K::ONE),
+        }),
+    }
+}
+
+/// Ways in which the internal structure of a tree can be found to be
+///     inconsistent by VEBTree::validate
+///
+/// Every variant names the node it was found at by its id, which is
+///     its position in the arena that holds it.  The aux trees have an
+///     arena of their own, so the same id can come up at more than one
+///     level of nesting.
+///
+/// # Variants
+/// * MinAboveMax: the cached minimum of a node is larger than its
+///   cached maximum
+/// * BoundNotPresent: a node caches a minimum or maximum that is not
+///   actually stored in it, or only caches one of the two
+/// * MinNotSmallest: a node stores a key smaller than its minimum
+/// * MaxNotLargest: a node stores a key larger than its maximum
+/// * KeyOutsideUniverse: a node stores a key that is not smaller than
+///   its universe
+/// * UniverseMismatch: a cluster or aux tree does not have the
+///   universe its place in the parent node calls for
+/// * ClusterMissingFromAux: a cluster holds keys but its index is not
+///   in the aux tree of its parent
+/// * EmptyClusterInAux: the aux tree of a node holds the index of a
+///   cluster that is empty or was never allocated
+/// * EmptyClusterKept: a sparse node still holds a cluster that has
+///   become empty
+/// * ClusterCountMismatch: the prefix counts of a node, used by rank
+///   and select, disagree with the number of elements in its clusters
+/// * CountMismatch: the element count of a node is not the number of
+///   elements stored in it
+/// * ValueMismatch: a node does not hold exactly one value for each of
+///   its keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvariantViolation {
+    MinAboveMax { node: usize },
+    BoundNotPresent { node: usize },
+    MinNotSmallest { node: usize },
+    MaxNotLargest { node: usize },
+    KeyOutsideUniverse { node: usize },
+    UniverseMismatch { node: usize, expected: usize, found: usize },
+    ClusterMissingFromAux { node: usize, cluster: usize },
+    EmptyClusterInAux { node: usize, cluster: usize },
+    EmptyClusterKept { node: usize, cluster: usize },
+    ClusterCountMismatch { node: usize },
+    CountMismatch { node: usize, expected: usize, found: usize },
+    ValueMismatch { node: usize },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InvariantViolation::MinAboveMax { node } => {
+                write!(f, "node {} has a minimum above its maximum", node)
+            }
+            InvariantViolation::BoundNotPresent { node } => {
+                write!(f, "node {} caches a bound that it does not store", node)
+            }
+            InvariantViolation::MinNotSmallest { node } => {
+                write!(f, "node {} stores a key below its minimum", node)
+            }
+            InvariantViolation::MaxNotLargest { node } => {
+                write!(f, "node {} stores a key above its maximum", node)
+            }
+            InvariantViolation::KeyOutsideUniverse { node } => {
+                write!(f, "node {} stores a key outside of its universe", node)
+            }
+            InvariantViolation::UniverseMismatch {
+                node,
+                expected,
+                found,
+            } => write!(
+                f,
+                "node {} has a universe of {} where {} was expected",
+                node, found, expected
+            ),
+            InvariantViolation::ClusterMissingFromAux { node, cluster } => write!(
+                f,
+                "cluster {} of node {} is populated but missing from its aux tree",
+                cluster, node
+            ),
+            InvariantViolation::EmptyClusterInAux { node, cluster } => write!(
+                f,
+                "cluster {} of node {} is in its aux tree but holds no keys",
+                cluster, node
+            ),
+            InvariantViolation::EmptyClusterKept { node, cluster } => write!(
+                f,
+                "cluster {} of sparse node {} is empty but still allocated",
+                cluster, node
+            ),
+            InvariantViolation::ClusterCountMismatch { node } => {
+                write!(f, "node {} miscounts the elements of its clusters", node)
+            }
+            InvariantViolation::CountMismatch {
+                node,
+                expected,
+                found,
+            } => write!(
+                f,
+                "node {} counts {} elements but stores {}",
+                node, found, expected
+            ),
+            InvariantViolation::ValueMismatch { node } => {
+                write!(f, "node {} does not hold one value per key", node)
+            }
+        }
+    }
+}
+
+impl Error for InvariantViolation {}
+
+/// Errors reported when reading a snapshot written by
+///     VEBTree::write_to
+///
+/// # Variants
+/// * Io: the reader failed for a reason other than running out of
+///   input
+/// * Truncated: the input ended before the snapshot did
+/// * BadMagic: the input does not start with a snapshot header
+/// * UnsupportedVersion: the snapshot was written in a format
+///   version this crate cannot read
+/// * KeyWidthMismatch: the snapshot was written from a tree with a
+///   different key type than the one being read into
+/// * ChecksumMismatch: the contents do not match the checksum they
+///   were written with
+/// * Corrupt: the header and checksum are valid but the contents
+///   do not describe a tree
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u16),
+    KeyWidthMismatch { expected: u32, found: u32 },
+    ChecksumMismatch { expected: u32, found: u32 },
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SnapshotError::Io(ref err) => write!(f, "failed to read snapshot: {}", err),
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::BadMagic => write!(f, "input is not a VEBTree snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "snapshot format version {} is not supported", version)
+            }
+            SnapshotError::KeyWidthMismatch { expected, found } => write!(
+                f,
+                "snapshot holds {}-bit keys but {}-bit keys were expected",
+                found, expected
+            ),
+            SnapshotError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "snapshot checksum {:#010x} does not match its contents ({:#010x})",
+                expected, found
+            ),
+            SnapshotError::Corrupt(reason) => write!(f, "snapshot is corrupt: {}", reason),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            SnapshotError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            SnapshotError::Truncated
+        } else {
+            SnapshotError::Io(err)
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use std::iter::FusedIterator;
+use std::ops::{Bound, RangeBounds};
+
+use key::VEBKey;
+use VEBTree;
+
+/// An iterator over the elements of a VEBTree in ascending order
+///
+/// Created by VEBTree::iter.  Each step is a findnext or findprev
+///     call, so walking the whole tree never touches empty clusters.
+///
+/// # Fields
+/// * tree: the tree being iterated over
+/// * front: the next element to yield from the front, None once
+///   the iterator is exhausted
+/// * back: the next element to yield from the back, None once
+///   the iterator is exhausted
+/// * remaining: the number of elements left to yield
+#[derive(Clone, Debug)]
+pub struct Iter<'a, K: VEBKey + 'a> {
+    tree: &'a VEBTree<K>,
+    front: Option<K>,
+    back: Option<K>,
+    remaining: usize,
+}
+
+impl<'a, K: VEBKey> Iter<'a, K> {
+    /// Creates an iterator over the whole of the given tree
+    pub(crate) fn new(tree: &'a VEBTree<K>) -> Self {
+        Iter {
+            tree,
+            front: tree.minimum(),
+            back: tree.maximum(),
+            remaining: tree.len(),
+        }
+    }
+}
+
+/// Advances a pair of cursors from the front, clearing both once they
+///     have met.
+///
+/// # Arguments
+/// * tree: the tree the cursors walk over
+/// * front: the next element to yield from the front
+/// * back: the next element to yield from the back
+///
+/// # Returns
+/// * The element that was at the front
+fn step_front<K: VEBKey>(
+    tree: &VEBTree<K>,
+    front: &mut Option<K>,
+    back: &mut Option<K>,
+) -> Option<K> {
+    let current = (*front)?;
+    if Some(current) == *back {
+        *front = None;
+        *back = None;
+    } else {
+        *front = tree.findnext(current);
+    }
+    Some(current)
+}
+
+/// Advances a pair of cursors from the back, clearing both once they
+///     have met.
+///
+/// # Arguments
+/// * tree: the tree the cursors walk over
+/// * front: the next element to yield from the front
+/// * back: the next element to yield from the back
+///
+/// # Returns
+/// * The element that was at the back
+fn step_back<K: VEBKey>(
+    tree: &VEBTree<K>,
+    front: &mut Option<K>,
+    back: &mut Option<K>,
+) -> Option<K> {
+    let current = (*back)?;
+    if Some(current) == *front {
+        *front = None;
+        *back = None;
+    } else {
+        *back = tree.findprev(current);
+    }
+    Some(current)
+}
+
+impl<'a, K: VEBKey> Iterator for Iter<'a, K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        let current = step_front(self.tree, &mut self.front, &mut self.back)?;
+        self.remaining -= 1;
+        Some(current)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K: VEBKey> DoubleEndedIterator for Iter<'a, K> {
+    fn next_back(&mut self) -> Option<K> {
+        let current = step_back(self.tree, &mut self.front, &mut self.back)?;
+        self.remaining -= 1;
+        Some(current)
+    }
+}
+
+impl<'a, K: VEBKey> ExactSizeIterator for Iter<'a, K> {}
+
+impl<'a, K: VEBKey> FusedIterator for Iter<'a, K> {}
+
+/// An owning iterator over the elements of a VEBTree in ascending order
+///
+/// Created by the IntoIterator implementation for VEBTree.
+///
+/// # Fields
+/// * tree: the tree being iterated over
+/// * front: the next element to yield from the front
+/// * back: the next element to yield from the back
+/// * remaining: the number of elements left to yield
+#[derive(Clone, Debug)]
+pub struct IntoIter<K: VEBKey> {
+    tree: VEBTree<K>,
+    front: Option<K>,
+    back: Option<K>,
+    remaining: usize,
+}
+
+impl<K: VEBKey> Iterator for IntoIter<K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        let current = step_front(&self.tree, &mut self.front, &mut self.back)?;
+        self.remaining -= 1;
+        Some(current)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K: VEBKey> DoubleEndedIterator for IntoIter<K> {
+    fn next_back(&mut self) -> Option<K> {
+        let current = step_back(&self.tree, &mut self.front, &mut self.back)?;
+        self.remaining -= 1;
+        Some(current)
+    }
+}
+
+impl<K: VEBKey> ExactSizeIterator for IntoIter<K> {}
+
+impl<K: VEBKey> FusedIterator for IntoIter<K> {}
+
+impl<K: VEBKey> IntoIterator for VEBTree<K> {
+    type Item = K;
+    type IntoIter = IntoIter<K>;
+
+    fn into_iter(self) -> IntoIter<K> {
+        IntoIter {
+            front: self.minimum(),
+            back: self.maximum(),
+            remaining: self.len(),
+            tree: self,
+        }
+    }
+}
+
+impl<'a, K: VEBKey> IntoIterator for &'a VEBTree<K> {
+    type Item = K;
+    type IntoIter = Iter<'a, K>;
+
+    fn into_iter(self) -> Iter<'a, K> {
+        self.iter()
+    }
+}
+
+/// Panics if a range is invalid under the same conditions as
+///     BTreeSet::range, when the start of the range is after the end,
+///     or when they are equal and both excluded
+pub(crate) fn check_bounds<K: VEBKey, R: RangeBounds<K>>(range: &R) {
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Excluded(start), Bound::Excluded(end)) if start == end => {
+            panic!("range start and end are equal and excluded in VEBTree")
+        }
+        (Bound::Included(start), Bound::Included(end))
+        | (Bound::Included(start), Bound::Excluded(end))
+        | (Bound::Excluded(start), Bound::Included(end))
+        | (Bound::Excluded(start), Bound::Excluded(end))
+            if start > end =>
+        {
+            panic!("range start is greater than range end in VEBTree")
+        }
+        _ => (),
+    }
+}
+
+/// An iterator over a sub-range of the elements of a VEBTree in
+///     ascending order
+///
+/// Created by VEBTree::range.  The ends of the range are found with
+///     ceil and floor, or findnext and findprev for excluded bounds,
+///     so no time is spent on elements outside of it.
+///
+/// # Fields
+/// * tree: the tree being iterated over
+/// * front: the next element to yield from the front, None once
+///   the iterator is exhausted
+/// * back: the next element to yield from the back, None once
+///   the iterator is exhausted
+#[derive(Clone, Debug)]
+pub struct Range<'a, K: VEBKey + 'a> {
+    tree: &'a VEBTree<K>,
+    front: Option<K>,
+    back: Option<K>,
+}
+
+impl<'a, K: VEBKey> Range<'a, K> {
+    /// Creates an iterator over the elements of the tree within the
+    ///     given bounds
+    ///
+    /// # Panics
+    /// * Under the same conditions as BTreeSet::range, when the start
+    ///   of the range is after the end, or when they are equal and
+    ///   both excluded
+    pub(crate) fn new<R: RangeBounds<K>>(tree: &'a VEBTree<K>, range: R) -> Self {
+        check_bounds(&range);
+        let front = match range.start_bound() {
+            Bound::Included(&start) => tree.ceil(start),
+            Bound::Excluded(&start) => tree.findnext(start),
+            Bound::Unbounded => tree.minimum(),
+        };
+        let back = match range.end_bound() {
+            Bound::Included(&end) => tree.floor(end),
+            Bound::Excluded(&end) => tree.findprev(end),
+            Bound::Unbounded => tree.maximum(),
+        };
+        match (front, back) {
+            (Some(first), Some(last)) if first <= last => Range { tree, front, back },
+            _ => Range {
+                tree,
+                front: None,
+                back: None,
+            },
+        }
+    }
+}
+
+impl<'a, K: VEBKey> Iterator for Range<'a, K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        step_front(self.tree, &mut self.front, &mut self.back)
+    }
+}
+
+impl<'a, K: VEBKey> DoubleEndedIterator for Range<'a, K> {
+    fn next_back(&mut self) -> Option<K> {
+        step_back(self.tree, &mut self.front, &mut self.back)
+    }
+}
+
+impl<'a, K: VEBKey> FusedIterator for Range<'a, K> {}
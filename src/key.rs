@@ -0,0 +1,134 @@
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ops::{Add, BitAnd, Shl, Shr, Sub};
+
+/// The set of unsigned integer types that can be used as keys in a
+///     VEBTree.
+///
+/// Implemented for u8, u16, u32, u64, u128 and usize.  The tree does
+///     all of its cluster arithmetic in the key type itself, so a
+///     `VEBTree<u16>` only ever stores u16s.
+pub trait VEBKey:
+    Copy
+    + Ord
+    + Debug
+    + Hash
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + BitAnd<Output = Self>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+{
+    /// The width of the key type in bits
+    const BITS: u32;
+
+    /// The key with value zero
+    const ZERO: Self;
+
+    /// The key with value one
+    const ONE: Self;
+
+    /// The largest value representable by the key type
+    const MAX: Self;
+
+    /// Converts a usize into the key type, truncating if it does
+    ///     not fit.
+    ///
+    /// # Arguments
+    /// * value: the usize to convert
+    ///
+    /// # Returns
+    /// * The value as a key
+    fn from_usize(value: usize) -> Self;
+
+    /// Converts the key into a usize, saturating at usize::MAX if it
+    ///     does not fit.
+    ///
+    /// # Arguments
+    /// * self: the key to convert
+    ///
+    /// # Returns
+    /// * The key as a usize
+    fn to_usize(self) -> usize;
+}
+
+macro_rules! impl_veb_key {
+    ($($t:ty),*) => {$(
+        impl VEBKey for $t {
+            const BITS: u32 = <$t>::BITS;
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+            const MAX: Self = <$t>::MAX;
+
+            fn from_usize(value: usize) -> Self {
+                value as $t
+            }
+
+            fn to_usize(self) -> usize {
+                usize::try_from(self).unwrap_or(usize::MAX)
+            }
+        }
+    )*};
+}
+
+impl_veb_key!(u8, u16, u32, u64, u128, usize);
+
+/// Converts a universe given as a number of keys into the largest key
+///     it holds
+///
+/// # Arguments
+/// * max_size: the number of keys in the universe
+///
+/// # Returns
+/// * max_size - 1 as a key, None for an empty universe
+///
+/// # Panics
+/// * If max_size is larger than the number of values the key type
+///   can represent
+pub(crate) fn max_key_for<K: VEBKey>(max_size: usize) -> Option<K> {
+    let max_key = max_size.checked_sub(1)?;
+    assert!(
+        max_key <= K::MAX.to_usize(),
+        "a universe of {} does not fit in a {}-bit key",
+        max_size,
+        K::BITS
+    );
+    Some(K::from_usize(max_key))
+}
+
+/// Converts the largest key of a universe back into the number of keys
+///     it holds, the inverse of max_key_for
+///
+/// # Arguments
+/// * max_key: the largest key of the universe, None if it is empty
+///
+/// # Returns
+/// * max_key + 1, saturating at usize::MAX for universes that do not
+///   fit in a usize
+pub(crate) fn universe_size<K: VEBKey>(max_key: Option<K>) -> usize {
+    max_key.map_or(0, |max_key| max_key.to_usize().saturating_add(1))
+}
+
+/// Returns the number of bits needed to write a key, 0 for zero
+///
+/// # Arguments
+/// * value: the key to measure
+///
+/// # Returns
+/// * The position of the highest set bit plus one
+pub(crate) fn bit_length<K: VEBKey>(value: K) -> u32 {
+    let (mut rest, mut bits) = (value, 0);
+    // Halve the remaining width each step until at most one bit is
+    // left, which works for every key width up to 128
+    for &step in &[64, 32, 16, 8, 4, 2, 1] {
+        if step < K::BITS && rest >> step != K::ZERO {
+            rest = rest >> step;
+            bits += step;
+        }
+    }
+    if rest != K::ZERO {
+        bits += 1;
+    }
+    bits
+}
@@ -2,101 +2,239 @@
 mod tests;
 
 #[cfg(test)]
-mod test_asymptotes;
+mod proptests;
 
-// Realized that this was the only useful data type to use as keys
-// for the tree.  usize is the default pointer size for the system.
-type T = usize;
+#[cfg(test)]
+extern crate proptest;
+
+#[cfg(feature = "serde")]
+extern crate serde;
+
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
+#[cfg(feature = "serde")]
+mod serialize;
+
+mod arena;
+mod bulk;
+mod error;
+mod iter;
+mod key;
+mod map;
+mod multiset;
+mod queue;
+mod set_ops;
+mod snapshot;
+mod stats;
+
+pub use error::{InvariantViolation, SnapshotError, VebError};
+pub use iter::{IntoIter, Iter, Range};
+pub use key::VEBKey;
+pub use map::VEBMap;
+pub use multiset::VebMultiset;
+pub use queue::VebPriorityQueue;
+pub use set_ops::{Difference, Intersection, SymmetricDifference, Union};
+pub use stats::{LevelStats, TreeStats};
+
+use arena::Arena;
+use error::check_key;
+use key::{max_key_for, universe_size};
+
+use std::ops::{Bound, RangeBounds};
+
+/// The largest universe a dense tree is rebuilt with when it is read
+///     back by read_from or deserialized.  Dense trees allocate their
+///     whole universe up front, so without a limit a few bytes of
+///     input could ask for more memory than the machine has.  Larger
+///     dense trees are loaded in the sparse layout instead, which
+///     holds the same keys in memory that grows with the keys.
+pub const MAX_LOADED_DENSE_UNIVERSE: usize = 1 << 24;
 
 /// An implementation of Van Emde Boas Trees in Rust
 ///
+/// The tree is generic over the key type K, which can be any of the
+///     unsigned integer types (see VEBKey).  It defaults to usize.
+///
+/// # Out of range keys
+/// A tree created with a max_size of U holds the keys 0..U, which
+///     is reported by universe().  with_max_key creates a tree by its
+///     largest key instead, which can also cover every value of K.
+///     Keys outside of the universe are never stored:
+/// * insert panics, since the key cannot be added
+/// * contains, search, delete, remove and take treat the key as
+///   absent, and the neighbour and range queries answer as if it
+///   were any other absent key
+/// * try_insert, try_delete and try_contains return
+///   VebError::OutOfRange instead, for callers that need to tell
+///   the two cases apart
+///
+/// # Memory layout
+/// Every node of the recursion is kept in one contiguous arena and
+///     refers to its clusters, and to the cluster counts behind rank
+///     and select, by index, so a dense tree is a handful of
+///     allocations no matter how large the universe is.  Cloning
+///     it copies those buffers in one go.  A dense tree still holds a
+///     node for every cluster of its universe, which heap_size_bytes
+///     and stats report.
+///
 /// # Fields
-/// * children: Vec<VEBTree> - the child VEBTrees of this tree
-/// * aux: Vec<VEBTree> - a single element Vec that holds to aux tree
-///     uses a Vec wrapper to allocate it on the heap without hardcore
-///     mutable reference nonsense.
-/// * max: Option<T> - The maximum of the currently stored elements,
-///     none if there are no stored elements, equal to min if there is
-///     only one element
-/// * min: Option<T> - The minimum of the currently stored elements,
-///     none if there are no stored elements, equal to max if there is
-///     only one element
-#[derive(Clone,Debug, PartialEq, Eq)]
-pub struct VEBTree {
-    children: Vec<VEBTree>,
-    aux: Vec<VEBTree>,
-    max: Option<T>,
-    min: Option<T>
+/// * arena: `Arena<K, ()>` - the nodes of the recursion, with no
+///   values attached to their keys
+/// * max_key: `Option<K>` - the largest key the tree can hold, None
+///   for a tree created with a max_size of 0
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VEBTree<K: VEBKey = usize> {
+    arena: Arena<K, ()>,
+    max_key: Option<K>,
+}
 
+/// Which neighbour VEBTree::nearest returns when a value is exactly
+///     halfway between two elements
+///
+/// # Variants
+/// * Lower: prefer the smaller element
+/// * Higher: prefer the larger element
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TieBreak {
+    Lower,
+    Higher,
 }
 
-impl VEBTree {
+impl<K: VEBKey> VEBTree<K> {
     /// Creates a new VEBTree with given max capacity.
     ///
     /// # Arguments
     /// * max_size: the maximum capacity with which to
-    ///     initialize the tree
+    ///   initialize the tree
     ///
     /// # Returns
     /// * A tree initialized to the maximum capacity
-    ///     specified
+    ///   specified
+    ///
+    /// # Panics
+    /// * If max_size is larger than the number of values the key
+    ///   type can represent
     pub fn new(max_size: usize) -> Self {
-        // Takes the square root of the max_size, then casts
-        // it back to an usize integer 
-        let self_size: usize;
-        let pass_size: usize;
-        if max_size <= 2 {
-            self_size = 0;
-            pass_size = 0;
+        Self::with_layout(max_key_for(max_size), false)
+    }
+
+    /// Creates a new VEBTree with given max capacity that only
+    ///     allocates clusters as values are inserted into them, and
+    ///     frees them again once they are empty.
+    ///
+    /// Memory use is proportional to the number of stored elements
+    ///     rather than to max_size, which makes 32 and 64-bit
+    ///     universes usable.
+    ///
+    /// # Arguments
+    /// * max_size: the maximum capacity of the tree
+    ///
+    /// # Returns
+    /// * An empty tree of the maximum capacity specified
+    ///
+    /// # Panics
+    /// * If max_size is larger than the number of values the key
+    ///   type can represent
+    pub fn new_sparse(max_size: usize) -> Self {
+        Self::with_layout(max_key_for(max_size), true)
+    }
+
+    /// Creates a new VEBTree holding the keys up to and including
+    ///     max_key, allocating every level of the recursion up front.
+    ///
+    /// Unlike new, the universe is given in the key type itself, so
+    ///     it is not limited by the width of a usize.
+    ///
+    /// # Arguments
+    /// * max_key: the largest key the tree can hold
+    ///
+    /// # Returns
+    /// * An empty tree for the keys 0..=max_key
+    pub fn with_max_key(max_key: K) -> Self {
+        Self::with_layout(Some(max_key), false)
+    }
+
+    /// Creates a new sparse VEBTree holding the keys up to and
+    ///     including max_key.  `with_max_key_sparse(K::MAX)` covers
+    ///     every value of the key type, e.g. all of u64 even where a
+    ///     usize is 32 bits wide.
+    ///
+    /// # Arguments
+    /// * max_key: the largest key the tree can hold
+    ///
+    /// # Returns
+    /// * An empty sparse tree for the keys 0..=max_key
+    pub fn with_max_key_sparse(max_key: K) -> Self {
+        Self::with_layout(Some(max_key), true)
+    }
+
+    /// Shared constructor for every layout and way of giving the
+    ///     universe
+    ///
+    /// # Arguments
+    /// * max_key: the largest key the tree can hold, None for a tree
+    ///   that holds nothing
+    /// * sparse: whether clusters should be allocated lazily
+    pub(crate) fn with_layout(max_key: Option<K>, sparse: bool) -> Self {
+        // An empty universe still gets a root, which is never reached
+        // since check_key rejects every key
+        let last = max_key.unwrap_or(K::ZERO);
+        let arena = if sparse {
+            Arena::new_sparse(last)
         } else {
-            let tmp = (max_size as f64).sqrt().ceil() as usize;
-            self_size = tmp;
-            pass_size = tmp;
-        }
-        let mut children_seed: Vec<VEBTree> = Vec::with_capacity(self_size);
-        let mut aux_seed: Vec<VEBTree> = Vec::with_capacity(1);
-        if pass_size > 0 {
-            for index in 0..self_size {
-                children_seed.push(Self::new(pass_size));
-            }
-            let mut aux = Self::new(pass_size);
-            aux_seed.push(aux);
-        }
-        let mut tree = VEBTree {
-            children: children_seed,
-            aux: aux_seed,
-            max: None,
-            min: None
+            Arena::new(last)
         };
-        return tree
+        VEBTree { arena, max_key }
+    }
+
+    /// Picks the layout of a tree rebuilt from input that may have
+    ///     been tampered with: the layout it was saved with, except
+    ///     that dense universes above MAX_LOADED_DENSE_UNIVERSE are
+    ///     rebuilt sparse so memory stays bounded by the input
+    ///
+    /// # Returns
+    /// * Whether to rebuild the tree sparse
+    pub(crate) fn loaded_sparse(max_key: Option<K>, sparse: bool) -> bool {
+        sparse || universe_size(max_key) > MAX_LOADED_DENSE_UNIVERSE
     }
-}
 
-impl VEBTree {
-    /// Returns the quotient of the given number with respect to the
-    ///     instance's number of children.
+    /// Gets the number of keys the tree can hold, every key must be
+    ///     smaller than this.
+    ///
     /// # Arguments
-    /// * self: the instance of the VEBTree
-    /// * value: the value to divide by the number of
-    ///     children
+    /// * self: the instance of VEBTree to operate on
     ///
     /// # Returns
-    /// * The quotient of the number w.r.t. self.children.len()
-    fn high(&self, value: T) -> T {
-        return value / self.children.len();
+    /// * The max_size the tree was created with, saturating at
+    ///   usize::MAX for universes too large for a usize (see
+    ///   max_key)
+    pub fn universe(&self) -> usize {
+        universe_size(self.max_key)
     }
 
-    /// Returns the modulus of the given number with respect to the
-    ///     instance's number of children.
+    /// Gets the largest key the tree can hold, which unlike universe
+    ///     is exact for every key type
+    ///
     /// # Arguments
-    /// * self: &Self - the instance of the VEBTree
-    /// * value: T==usize - the value to modulo by the number of
-    ///     children
+    /// * self: the instance of VEBTree to operate on
     ///
     /// # Returns
-    /// * The modulus of the number w.r.t. self.children.len()
-    fn low(&self, value: T) -> T {
-        return value % self.children.len();
+    /// * The largest key of the universe, None if the tree was
+    ///   created with a max_size of 0
+    pub fn max_key(&self) -> Option<K> {
+        self.max_key
+    }
+
+    /// Returns whether the tree allocates its clusters lazily
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    ///
+    /// # Returns
+    /// * true if the tree was created with new_sparse
+    pub fn is_sparse(&self) -> bool {
+        self.arena.is_sparse()
     }
 
     /// Returns whether or not the given element is in the tree
@@ -106,33 +244,25 @@ impl VEBTree {
     /// * value: the value for which to check membership
     ///
     /// # Returns
-    /// * Whether or not the value is contained in the tree
-    pub fn contains(&self, value: T) -> bool {
-        match self.min {
-            Some(min_val) => {
-                if value == min_val {
-                    return true;
-                } else {
-                    match self.max {
-                        Some(max_val) => {
-                            if value == max_val {
-                                return true;
-                            } else {
-                                if self.children.len() > 0 {
-                                    return self
-                                        .children[self.high(value)]
-                                        .contains(self.low(value));
-                                } else {
-                                    return false;
-                                }
-                            }
-                        },
-                        None => {return false;}
-                    }
-                }
-            },
-            None => {return false;}
-        }
+    /// * Whether or not the value is contained in the tree, false
+    ///   for values outside of the universe
+    pub fn contains(&self, value: K) -> bool {
+        check_key(value, self.max_key).is_ok() && self.arena.contains(value)
+    }
+
+    /// Returns whether or not the given element is in the tree,
+    ///     rejecting values outside of the universe.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBTree
+    /// * value: the value for which to check membership
+    ///
+    /// # Returns
+    /// * Whether or not the value is contained in the tree, or
+    ///   VebError::OutOfRange if it is outside of the universe
+    pub fn try_contains(&self, value: K) -> Result<bool, VebError<K>> {
+        check_key(value, self.max_key)?;
+        Ok(self.arena.contains(value))
     }
 
     /// Searches the tree for the given value and returns the value if
@@ -144,209 +274,151 @@ impl VEBTree {
     ///
     /// # Returns
     /// * The value being searched for or None if the value
-    ///     is not in the tree.
-    pub fn search(&self, value: T) -> Option<T> {
-        let min_val = self.min?;
-        if value == min_val {
-            return self.min;
-        }
-        let max_val = self.max?;
-        if value == max_val {
-            return self.max;
-        }
-        if self.children.len() == 0 {
-            return None;
-        } else {
-            let local_idx = self.high(value);
-            let pass_value = self.low(value);
-            self.children[local_idx].minimum()?;
-            let result = self.children[local_idx].search(pass_value)?;
-            return Some(result+local_idx*self.children.len());
-        }
+    ///   is not in the tree.
+    pub fn search(&self, value: K) -> Option<K> {
+        check_key(value, self.max_key).ok()?;
+        self.arena.search(value)
     }
 
-    /// Convenience function to handle making the recursive insert calls
-    ///     into the child trees.
+    /// Insert a value into the array, does nothing if the value
+    ///     is already present.  Same semantics as BTreeSet::insert.
     ///
     /// # Arguments
     /// * self: the instance of the VEBTree to operate on
     /// * value: the value to insert into the tree
-    fn insert_into_tree(&mut self, value: T) {
-        if self.children.len() > 0 {
-            let local_idx = self.high(value);
-            let pass_value = self.low(value);
-            match self.children[local_idx].minimum() {
-                Some(min_value) => (),
-                None => {
-                    self.aux[0].insert(local_idx);
-                }
-            };
-            self.children[local_idx].insert(pass_value);
+    ///
+    /// # Returns
+    /// * true if the value was newly inserted, false if it was
+    ///   already present
+    ///
+    /// # Panics
+    /// * If the value is outside of the universe, see try_insert
+    pub fn insert(&mut self, value: K) -> bool {
+        match self.try_insert(value) {
+            Ok(inserted) => inserted,
+            Err(err) => panic!("{}", err),
         }
     }
 
-    /// Insert a value into the array, does nothing if the value
-    ///     is already present.
+    /// Insert a value into the array, rejecting values outside of the
+    ///     universe.
     ///
     /// # Arguments
     /// * self: the instance of the VEBTree to operate on
     /// * value: the value to insert into the tree
-    pub fn insert(&mut self, value: T) {
-        match self.min {
-            Some(min_value) => {
-                if value == min_value {
-                    return;
-                } else {
-                    match self.max {
-                        Some(max_value) => {
-                            if max_value == value {
-                                return;
-                            } else {
-                                if value < min_value {
-                                    self.min = Some(value);
-                                    self.insert_into_tree(value);
-                                    return;
-                                } else if value > max_value {
-                                    self.max = Some(value);
-                                    self.insert_into_tree(value);
-                                    return;
-                                } else {
-                                    self.insert_into_tree(value);
-                                    return;
-                                }
-                            }
-                        },
-                        None => {
-                            self.max = Some(value);
-                            self.insert_into_tree(value);
-                            return;
-                        }
-                    };
-                }
-            },
-            None => {
-                self.min = Some(value);
-                self.max = Some(value);
-                self.insert_into_tree(value);
-                return;
-            }
-        };
-
+    ///
+    /// # Returns
+    /// * Whether the value was newly inserted, or
+    ///   VebError::OutOfRange if it is outside of the universe
+    pub fn try_insert(&mut self, value: K) -> Result<bool, VebError<K>> {
+        check_key(value, self.max_key)?;
+        Ok(self.arena.insert(value, ()).is_none())
     }
 
-    /// Convenience function to manage making recursive delete calls
-    ///     into the VEBTree
+    /// Deletes an element from the VEBTree, does nothing if the value
+    ///     is not present.  Same semantics as BTreeSet::remove.
     ///
     /// # Arguments
     /// * self: the instance of the VEBTree to operate on
-    /// * value: the value to delete from the child trees
-    fn delete_from_tree(&mut self, value: T) {
-        if self.children.len() > 0 {
-            let local_idx = self.high(value);
-            let pass_value = self.low(value);
-            self.children[local_idx].delete(pass_value);
-            if self.children[local_idx].minimum() == None {
-                self.aux[0].delete(local_idx);
-            }
-        }
+    /// * value: the value to delete from the tree
+    ///
+    /// # Returns
+    /// * true if the value was present and has been removed, false
+    ///   if it was not in the tree
+    pub fn delete(&mut self, value: K) -> bool {
+        self.take(value).is_some()
     }
 
-    /// Deletes an element from the VEBTree
+    /// Deletes an element from the VEBTree, rejecting values outside
+    ///     of the universe.
     ///
     /// # Arguments
     /// * self: the instance of the VEBTree to operate on
     /// * value: the value to delete from the tree
-    pub fn delete(&mut self, value: T){
-        match self.min {
-            Some(min_value) => {
-                match self.max {
-                    Some(max_value) => {
-                        if max_value == min_value {
-                            self.delete_from_tree(value);
-                            self.min = None;
-                            self.max = None;
-                            return;
-                        } else if self.children.len() == 0 {
-                            if value == min_value {
-                                self.min = Some(max_value);
-                            } else {
-                                self.max = Some(min_value);
-                            }
-                        } else if value == min_value {
-                            self.delete_from_tree(value);
-                            let first_populated = self.aux[0].minimum();
-                            match first_populated {
-                                Some(first_cluster) => {
-                                    let new_min = self
-                                        .children[first_cluster as usize]
-                                        .minimum();
-                                    match new_min {
-                                        Some(min) => {
-                                            self.min = Some((first_cluster
-                                                            * self.children.len() as T
-                                                             + min));
-                                            return;
-                                        },
-                                        // Not sure how one gets here, probably
-                                        // going to panic because structure corruption
-                                        None => {
-                                            panic!("Data structure appears corrupt");
-                                        }
-                                    };
-                                },
-                                None => {
-                                    self.max = None;
-                                    self.min = None;
-                                }
-                            };
-                        } else if value == max_value {
-                            self.delete_from_tree(value);
-                            let last_populated = self.aux[0].maximum();
-                            match last_populated {
-                                Some(last_cluster) => {
-                                    let new_max = self
-                                        .children[last_cluster as usize]
-                                        .maximum();
-                                    match new_max {
-                                        Some(max) => {
-                                            self.max = Some((last_cluster
-                                                             * self.children.len() as T
-                                                             + max));
-                                            return;
-                                        },
-                                        None => {
-                                            panic!("Data structure appears corrupt");
-                                        }
-                                    };
-                                },
-                                None => {
-                                    self.max = None;
-                                    self.min = None;
-                                }
-                            };
-                        } else if value < max_value && value > min_value {
-                            self.delete_from_tree(value);
-                            return;
-                        } else {
-                            return;
-                        }
-
-                    },
-                    None => {
-                        // This case shouldn't happen as there should always
-                        // be a max when there is a min.
-                        self.min = None;
-                        self.max = None;
-                        return;
-                    }
-                };
-            },
-            None => {
-                self.min = None;
-                self.max = None;
-                return;
-            }
-        };
+    ///
+    /// # Returns
+    /// * Whether the value was present and has been removed, or
+    ///   VebError::OutOfRange if it is outside of the universe
+    pub fn try_delete(&mut self, value: K) -> Result<bool, VebError<K>> {
+        check_key(value, self.max_key)?;
+        Ok(self.delete(value))
+    }
+
+    /// Removes an element from the VEBTree, an alias for delete that
+    ///     matches the BTreeSet naming.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBTree to operate on
+    /// * value: the value to remove from the tree
+    ///
+    /// # Returns
+    /// * true if the value was present and has been removed, false
+    ///   if it was not in the tree
+    pub fn remove(&mut self, value: K) -> bool {
+        self.delete(value)
+    }
+
+    /// Removes an element from the VEBTree and returns it.  Same
+    ///     semantics as BTreeSet::take.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBTree to operate on
+    /// * value: the value to remove from the tree
+    ///
+    /// # Returns
+    /// * The removed value, None if it was not in the tree
+    pub fn take(&mut self, value: K) -> Option<K> {
+        check_key(value, self.max_key).ok()?;
+        self.arena.remove(value)?;
+        Some(value)
+    }
+
+    /// Removes the smallest element of the tree and returns it, in a
+    ///     single descent rather than a minimum followed by a delete.
+    ///     Same semantics as BTreeSet::pop_first.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBTree to operate on
+    ///
+    /// # Returns
+    /// * The removed minimum, None if the tree is empty
+    pub fn pop_min(&mut self) -> Option<K> {
+        self.arena.pop_min().map(|(value, ())| value)
+    }
+
+    /// Removes the largest element of the tree and returns it, in a
+    ///     single descent rather than a maximum followed by a delete.
+    ///     Same semantics as BTreeSet::pop_last.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBTree to operate on
+    ///
+    /// # Returns
+    /// * The removed maximum, None if the tree is empty
+    pub fn pop_max(&mut self) -> Option<K> {
+        self.arena.pop_max().map(|(value, ())| value)
+    }
+
+    /// Gets the number of elements currently stored, in constant time
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    ///
+    /// # Returns
+    /// * The number of elements in the tree
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Returns whether the tree holds no elements
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    ///
+    /// # Returns
+    /// * Whether the tree is empty
+    pub fn is_empty(&self) -> bool {
+        self.arena.len() == 0
     }
 
     /// Gets the minimum of the currently stored elements
@@ -356,8 +428,8 @@ impl VEBTree {
     ///
     /// # Returns
     /// * The minimum element currently stored in the tree
-    pub fn minimum(&self) -> Option<T> {
-        return self.min;
+    pub fn minimum(&self) -> Option<K> {
+        self.arena.minimum()
     }
 
     /// Gets the maximum of the currently stored elements
@@ -367,121 +439,269 @@ impl VEBTree {
     ///
     /// # Returns
     /// * The maximum element currently stored in the tree
-    pub fn maximum(&self) -> Option<T> {
-        return self.max;
+    pub fn maximum(&self) -> Option<K> {
+        self.arena.maximum()
     }
 
     /// Finds the next consecutive element currently in the tree
     ///
+    /// Defined for every value of K: 'value' does not have to be in
+    ///     the tree, or even inside its universe.
+    ///
     /// # Arguments
     /// * self: the instance of VEBTree to operate on.
     /// * value: the value to find the successor of.
     ///
     /// # Returns
-    /// * The successor of 'value' or None if not found
-    pub fn findnext(&self, value: T) -> Option<T> {
-        if self.children.len() == 0 {
-            let min_val = self.min?;
-            let max_val = self.max?;
-            if value == 0 && max_val == 1 {
-                return self.max;
-            } else {
-                return None;
-            }
-        } else {
-            match self.min {
-                Some(min_value) => {
-                    if value < min_value {
-                        return self.min
-                    }
-                },
-                None => ()
-            };
-            let cur_cluster_max = self.children[self.high(value)].maximum();
-            match cur_cluster_max {
-                Some(max_value) => {
-                    if self.low(value) < max_value {
-                        let offset = self.children[self.high(value)]
-                            .findnext(self.low(value))?;
-                        return match self.children[self.high(value)].search(offset) {
-                            Some(n) => {
-                                return Some(n + self.high(value)*self.children.len());
-                            },
-                            None => {
-                                return None;
-                            }
-                        };
-                    }
-                },
-                None => ()
-            };
-            let next_cluster = self.aux[0].findnext(self.high(value))?;
-            let offset = self.children[next_cluster].minimum()?;
-            return match self.children[next_cluster].search(offset) {
-                Some(n) => {
-                    return Some(n + next_cluster*self.children.len());
-                },
-                None => {
-                    return None;
-                }
-            };
-        }
+    /// * The smallest element greater than 'value', the minimum for
+    ///   any value below it, None if 'value' is at or past the
+    ///   maximum
+    pub fn findnext(&self, value: K) -> Option<K> {
+        self.arena.findnext(value)
     }
 
     /// Finds the immediate previous element currently in the array
     ///
+    /// Defined for every value of K: 'value' does not have to be in
+    ///     the tree, or even inside its universe.
+    ///
     /// # Arguments
     /// * self: the instance of VEBTree to operate on
     /// * value: the value to find the predecessor of
     ///
     /// # Returns
-    /// * The predecessor of 'value' or None if not found
-    pub fn findprev(&self, value: T) -> Option<T> {
-        if self.children.len() == 0 {
-            let max_value = self.maximum()?;
-            let min_value = self.minimum()?;
-            if max_value == value && max_value != min_value {
-                return self.min;
-            } else {
-                return None;
-            }
-        } else {
-            match self.maximum() {
-                Some(max_value) => {
-                    if value > max_value {
-                        return self.max;
-                    }
-                },
-                None => ()
-            };
-            let cur_cluster_min = self.children[self.high(value)].minimum();
-            match cur_cluster_min {
-                Some(min_value) => {
-                    if self.low(value) > min_value {
-                        let offset = self.children[self.high(value)]
-                            .findprev(self.low(value))?;
-                        return match self.children[self.high(value)].search(offset) {
-                            Some(n) => {
-                                return Some(n + self.high(value)*self.children.len());
-                            },
-                            None => {
-                                return None;
-                            }
-                        };
-                    }
-                },
-                None => ()
-            };
-            let next_cluster = self.aux[0].findprev(self.high(value))?;
-            let offset = self.children[next_cluster].maximum()?;
-            return match self.children[next_cluster].search(offset) {
-                Some(n) => {
-                    return Some(n + next_cluster*self.children.len());
-                },
-                None => {
-                    return None;
+    /// * The largest element smaller than 'value', the maximum for
+    ///   any value above it, None if 'value' is at or before the
+    ///   minimum
+    pub fn findprev(&self, value: K) -> Option<K> {
+        self.arena.findprev(value)
+    }
+
+    /// Finds the smallest element that is at least a value, in a
+    ///     single traversal rather than a contains followed by a
+    ///     findnext.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * value: the lower bound to search from
+    ///
+    /// # Returns
+    /// * 'value' itself if it is in the tree, otherwise its successor,
+    ///   None if every element is smaller
+    pub fn ceil(&self, value: K) -> Option<K> {
+        self.arena.ceil(value)
+    }
+
+    /// Finds the largest element that is at most a value, in a single
+    ///     traversal rather than a contains followed by a findprev.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * value: the upper bound to search from
+    ///
+    /// # Returns
+    /// * 'value' itself if it is in the tree, otherwise its
+    ///   predecessor, None if every element is larger
+    pub fn floor(&self, value: K) -> Option<K> {
+        self.arena.floor(value)
+    }
+
+    /// Alias for ceil, the smallest element that is at least a value
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * value: the lower bound to search from
+    ///
+    /// # Returns
+    /// * The same as ceil
+    pub fn lower_bound(&self, value: K) -> Option<K> {
+        self.ceil(value)
+    }
+
+    /// Alias for floor, the largest element that is at most a value.
+    ///     Unlike C++'s upper_bound this includes the value itself,
+    ///     findnext is the strict version.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * value: the upper bound to search from
+    ///
+    /// # Returns
+    /// * The same as floor
+    pub fn upper_bound(&self, value: K) -> Option<K> {
+        self.floor(value)
+    }
+
+    /// Finds the element closest to a value
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * value: the value to search around
+    /// * tie_break: which element to return when the floor and ceil
+    ///   of 'value' are equally far from it
+    ///
+    /// # Returns
+    /// * 'value' itself if it is in the tree, otherwise whichever of
+    ///   its neighbours is closer, None if the tree is empty
+    pub fn nearest(&self, value: K, tie_break: TieBreak) -> Option<K> {
+        let below = self.floor(value);
+        if below == Some(value) {
+            return below;
+        }
+        match (below, self.ceil(value)) {
+            (Some(below), Some(above)) => {
+                let (down, up) = (value - below, above - value);
+                if down < up || (down == up && tie_break == TieBreak::Lower) {
+                    Some(below)
+                } else {
+                    Some(above)
                 }
-            };
+            }
+            (below, above) => below.or(above),
         }
     }
+
+    /// Returns an iterator over the elements of the tree in
+    ///     ascending order.  It can also be walked backwards.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to iterate over
+    ///
+    /// # Returns
+    /// * An iterator over the elements of the tree
+    pub fn iter(&self) -> Iter<'_, K> {
+        Iter::new(self)
+    }
+
+    /// Returns an iterator over the elements of the tree that fall
+    ///     within a range, in ascending order.  Behaves like
+    ///     BTreeSet::range for every kind of bound.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to iterate over
+    /// * range: the bounds of the elements to visit, e.g. `a..b`
+    ///
+    /// # Returns
+    /// * An iterator over the elements within the range
+    ///
+    /// # Panics
+    /// * If the start of the range is after the end, or if they are
+    ///   equal and both excluded
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K> {
+        Range::new(self, range)
+    }
+
+    /// Counts the elements of the tree that fall within a range,
+    ///     using rank rather than visiting them.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * range: the bounds of the elements to count, e.g. `a..=b`
+    ///
+    /// # Returns
+    /// * The number of elements within the range
+    ///
+    /// # Panics
+    /// * Under the same conditions as range
+    pub fn range_count<R: RangeBounds<K>>(&self, range: R) -> usize {
+        iter::check_bounds(&range);
+        let below_start = match range.start_bound() {
+            Bound::Included(&start) => self.rank(start),
+            Bound::Excluded(&start) => self.rank(start) + self.contains(start) as usize,
+            Bound::Unbounded => 0,
+        };
+        let below_end = match range.end_bound() {
+            Bound::Included(&end) => self.rank(end) + self.contains(end) as usize,
+            Bound::Excluded(&end) => self.rank(end),
+            Bound::Unbounded => self.len(),
+        };
+        below_end.saturating_sub(below_start)
+    }
+
+    /// Counts the elements of the tree that are smaller than a value
+    ///
+    /// Every node keeps prefix counts of its clusters, so each level
+    ///     costs one step per bit of its cluster index, O(log U) in
+    ///     all for a universe of U rather than the O(log log U) of a
+    ///     lookup.  Keeping the counts current adds the same O(log U)
+    ///     to insert and remove.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * value: the value to rank, which does not need to be in the
+    ///   tree
+    ///
+    /// # Returns
+    /// * The number of elements smaller than 'value'
+    pub fn rank(&self, value: K) -> usize {
+        self.arena.rank(value)
+    }
+
+    /// Finds the k-th smallest element of the tree, the inverse of
+    ///     rank, in O(log U) for a universe of U.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * k: the 0-based position of the element in ascending order
+    ///
+    /// # Returns
+    /// * The element with exactly k smaller elements, None if the
+    ///   tree holds k or fewer elements
+    pub fn select(&self, k: usize) -> Option<K> {
+        self.arena.select(k)
+    }
+
+    /// Checks the internal structure of the tree, for tracking down
+    ///     bugs rather than for use on every operation: it visits every
+    ///     node, so it takes time linear in the size of the tree (in
+    ///     the universe, for a dense tree).
+    ///
+    /// Every node must have min <= max, with both actually stored in
+    ///     it and everything else in its clusters between them.  The
+    ///     aux tree of a node must hold exactly the indices of its
+    ///     non-empty clusters, and the element count of each node must
+    ///     match what is stored below it.  With the debug-invariants
+    ///     feature enabled this runs after every insert and delete,
+    ///     panicking on the first violation.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to check
+    ///
+    /// # Returns
+    /// * Ok if every invariant holds, otherwise the first
+    ///   InvariantViolation found
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        self.arena.validate()
+    }
+
+    /// Estimates the heap memory the tree uses, in time linear in the
+    ///     number of allocated nodes.
+    ///
+    /// A dense tree allocates its whole recursion up front, so this is
+    ///     mostly decided by the universe.  A sparse tree grows and
+    ///     shrinks with the keys it holds.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to measure
+    ///
+    /// # Returns
+    /// * The number of heap bytes held by the tree, not counting the
+    ///   VEBTree value itself
+    pub fn heap_size_bytes(&self) -> usize {
+        self.arena.heap_size_bytes()
+    }
+
+    /// Reports how the nodes of the tree are spread over the levels of
+    ///     the recursion, how many of the allocated clusters are in
+    ///     use, and how the heap memory splits between the clusters
+    ///     and the aux trees that index them.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to measure
+    ///
+    /// # Returns
+    /// * The statistics of the tree, see TreeStats
+    pub fn stats(&self) -> TreeStats {
+        self.arena.stats()
+    }
 }
@@ -0,0 +1,357 @@
+use error::{check_key, InvariantViolation};
+use key::{max_key_for, universe_size, VEBKey};
+use arena::Arena;
+use stats::TreeStats;
+
+/// A Van Emde Boas tree that stores a value alongside each key
+///
+/// Uses the same cluster/aux recursion as VEBTree, the values are
+///     kept in the leaves next to the keys they belong to.  Keys
+///     outside of the universe are handled the same way as in
+///     VEBTree: insert panics and every other operation treats them
+///     as absent.
+///
+/// # Fields
+/// * arena: `Arena<K, V>` - the nodes of the recursion
+/// * max_key: `Option<K>` - the largest key the map can hold, None
+///   for a map created with a max_size of 0
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VEBMap<K: VEBKey, V> {
+    arena: Arena<K, V>,
+    max_key: Option<K>,
+}
+
+impl<K: VEBKey, V> VEBMap<K, V> {
+    /// Creates a new VEBMap with given max capacity.
+    ///
+    /// # Arguments
+    /// * max_size: the maximum capacity with which to
+    ///   initialize the map
+    ///
+    /// # Returns
+    /// * A map initialized to the maximum capacity
+    ///   specified
+    ///
+    /// # Panics
+    /// * If max_size is larger than the number of values the key
+    ///   type can represent
+    pub fn new(max_size: usize) -> Self {
+        Self::with_layout(max_key_for(max_size), false)
+    }
+
+    /// Creates a new VEBMap with given max capacity that only
+    ///     allocates clusters as keys are inserted into them.
+    ///
+    /// # Arguments
+    /// * max_size: the maximum capacity of the map
+    ///
+    /// # Returns
+    /// * An empty map of the maximum capacity specified
+    ///
+    /// # Panics
+    /// * If max_size is larger than the number of values the key
+    ///   type can represent
+    pub fn new_sparse(max_size: usize) -> Self {
+        Self::with_layout(max_key_for(max_size), true)
+    }
+
+    /// Creates a new VEBMap for the keys up to and including max_key,
+    ///     see VEBTree::with_max_key.
+    ///
+    /// # Arguments
+    /// * max_key: the largest key the map can hold
+    ///
+    /// # Returns
+    /// * An empty map for the keys 0..=max_key
+    pub fn with_max_key(max_key: K) -> Self {
+        Self::with_layout(Some(max_key), false)
+    }
+
+    /// Creates a new sparse VEBMap for the keys up to and including
+    ///     max_key, see VEBTree::with_max_key_sparse.
+    ///
+    /// # Arguments
+    /// * max_key: the largest key the map can hold
+    ///
+    /// # Returns
+    /// * An empty sparse map for the keys 0..=max_key
+    pub fn with_max_key_sparse(max_key: K) -> Self {
+        Self::with_layout(Some(max_key), true)
+    }
+
+    /// Shared constructor for every layout and way of giving the
+    ///     universe, see VEBTree::with_layout
+    fn with_layout(max_key: Option<K>, sparse: bool) -> Self {
+        let last = max_key.unwrap_or(K::ZERO);
+        let arena = if sparse {
+            Arena::new_sparse(last)
+        } else {
+            Arena::new(last)
+        };
+        VEBMap { arena, max_key }
+    }
+
+    /// Inserts a key and value into the map, replacing the value if
+    ///     the key is already present.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    /// * key: the key to insert
+    /// * value: the value to store with the key
+    ///
+    /// # Returns
+    /// * The value previously stored with 'key', None if the key
+    ///   is new
+    ///
+    /// # Panics
+    /// * If the key is outside of the universe
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Err(err) = check_key(key, self.max_key) {
+            panic!("{}", err);
+        }
+        self.arena.insert(key, value)
+    }
+
+    /// Gets a reference to the value stored with a key
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    /// * key: the key to look up
+    ///
+    /// # Returns
+    /// * The value stored with 'key' or None if it is not present
+    pub fn get(&self, key: K) -> Option<&V> {
+        check_key(key, self.max_key).ok()?;
+        self.arena.get(key)
+    }
+
+    /// Gets a mutable reference to the value stored with a key
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    /// * key: the key to look up
+    ///
+    /// # Returns
+    /// * The value stored with 'key' or None if it is not present
+    pub fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        check_key(key, self.max_key).ok()?;
+        self.arena.get_mut(key)
+    }
+
+    /// Returns whether or not the given key is in the map
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap
+    /// * key: the key for which to check membership
+    ///
+    /// # Returns
+    /// * Whether or not the key is contained in the map
+    pub fn contains_key(&self, key: K) -> bool {
+        check_key(key, self.max_key).is_ok() && self.arena.contains(key)
+    }
+
+    /// Removes a key from the map
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    /// * key: the key to remove
+    ///
+    /// # Returns
+    /// * The value that was stored with 'key', None if the key
+    ///   was not present
+    pub fn remove(&mut self, key: K) -> Option<V> {
+        check_key(key, self.max_key).ok()?;
+        self.arena.remove(key)
+    }
+
+    /// Removes the smallest key from the map and returns it with its
+    ///     value, in a single descent
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    ///
+    /// # Returns
+    /// * The removed minimum key and its value, None if the map is
+    ///   empty
+    pub fn pop_min(&mut self) -> Option<(K, V)> {
+        self.arena.pop_min()
+    }
+
+    /// Removes the largest key from the map and returns it with its
+    ///     value, in a single descent
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    ///
+    /// # Returns
+    /// * The removed maximum key and its value, None if the map is
+    ///   empty
+    pub fn pop_max(&mut self) -> Option<(K, V)> {
+        self.arena.pop_max()
+    }
+
+    /// Gets the number of keys the map can hold, every key must be
+    ///     smaller than this.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    ///
+    /// # Returns
+    /// * The max_size the map was created with, saturating at
+    ///   usize::MAX for universes too large for a usize
+    pub fn universe(&self) -> usize {
+        universe_size(self.max_key)
+    }
+
+    /// Gets the largest key the map can hold, see VEBTree::max_key
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    ///
+    /// # Returns
+    /// * The largest key of the universe, None if the map was
+    ///   created with a max_size of 0
+    pub fn max_key(&self) -> Option<K> {
+        self.max_key
+    }
+
+    /// Gets the number of keys currently stored, in constant time
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    ///
+    /// # Returns
+    /// * The number of keys in the map
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Returns whether the map holds no keys
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    ///
+    /// # Returns
+    /// * Whether the map is empty
+    pub fn is_empty(&self) -> bool {
+        self.arena.len() == 0
+    }
+
+    /// Gets the smallest key in the map along with its value
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    ///
+    /// # Returns
+    /// * The minimum key and its value, None if the map is empty
+    pub fn minimum(&self) -> Option<(K, &V)> {
+        self.entry(self.arena.minimum()?)
+    }
+
+    /// Gets the largest key in the map along with its value
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    ///
+    /// # Returns
+    /// * The maximum key and its value, None if the map is empty
+    pub fn maximum(&self) -> Option<(K, &V)> {
+        self.entry(self.arena.maximum()?)
+    }
+
+    /// Finds the next consecutive key in the map along with its value
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    /// * key: the key to find the successor of
+    ///
+    /// # Returns
+    /// * The successor of 'key' and its value or None if not found
+    pub fn findnext(&self, key: K) -> Option<(K, &V)> {
+        self.entry(self.arena.findnext(key)?)
+    }
+
+    /// Finds the immediate previous key in the map along with its value
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    /// * key: the key to find the predecessor of
+    ///
+    /// # Returns
+    /// * The predecessor of 'key' and its value or None if not found
+    pub fn findprev(&self, key: K) -> Option<(K, &V)> {
+        self.entry(self.arena.findprev(key)?)
+    }
+
+    /// Finds the smallest key that is at least 'key', along with its
+    ///     value
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    /// * key: the lower bound to search from
+    ///
+    /// # Returns
+    /// * 'key' itself if it is in the map, otherwise its successor,
+    ///   with its value.  None if every key is smaller.
+    pub fn ceil(&self, key: K) -> Option<(K, &V)> {
+        self.entry(self.arena.ceil(key)?)
+    }
+
+    /// Finds the largest key that is at most 'key', along with its
+    ///     value
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to operate on
+    /// * key: the upper bound to search from
+    ///
+    /// # Returns
+    /// * 'key' itself if it is in the map, otherwise its predecessor,
+    ///   with its value.  None if every key is larger.
+    pub fn floor(&self, key: K) -> Option<(K, &V)> {
+        self.entry(self.arena.floor(key)?)
+    }
+
+    /// Checks the internal structure of the map, see
+    ///     VEBTree::validate.  This also checks that every key has
+    ///     exactly one value.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to check
+    ///
+    /// # Returns
+    /// * Ok if every invariant holds, otherwise the first
+    ///   InvariantViolation found
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        self.arena.validate()
+    }
+
+    /// Estimates the heap memory the map uses, see
+    ///     VEBTree::heap_size_bytes.  Values are counted by their size
+    ///     only, heap memory they own themselves is not followed.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to measure
+    ///
+    /// # Returns
+    /// * The number of heap bytes held by the map
+    pub fn heap_size_bytes(&self) -> usize {
+        self.arena.heap_size_bytes()
+    }
+
+    /// Reports how the nodes of the map are spread over the levels of
+    ///     the recursion, see VEBTree::stats
+    ///
+    /// # Arguments
+    /// * self: the instance of the VEBMap to measure
+    ///
+    /// # Returns
+    /// * The statistics of the map, see TreeStats
+    pub fn stats(&self) -> TreeStats {
+        self.arena.stats()
+    }
+
+    /// Pairs a key known to be in the map with its value.
+    fn entry(&self, key: K) -> Option<(K, &V)> {
+        self.arena.get(key).map(|value| (key, value))
+    }
+}
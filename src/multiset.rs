@@ -0,0 +1,265 @@
+use error::{check_key, VebError};
+use key::VEBKey;
+use map::VEBMap;
+
+/// A Van Emde Boas tree that counts how many times each key was
+///     inserted
+///
+/// Only the distinct keys are stored in the tree, each with its count,
+///     so the neighbour queries behave exactly like VEBTree::findnext
+///     and VEBTree::findprev over the distinct keys.  Keys outside of
+///     the universe are handled the same way as in VEBTree: insert
+///     panics and every other operation treats them as absent.
+///
+/// # Fields
+/// * counts: `VEBMap<K, usize>` - the number of copies of each key,
+///   never holding a count of 0
+/// * len: usize - the total number of copies of all keys
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VebMultiset<K: VEBKey> {
+    counts: VEBMap<K, usize>,
+    len: usize,
+}
+
+impl<K: VEBKey> VebMultiset<K> {
+    /// Creates a new empty VebMultiset with given max capacity.
+    ///
+    /// # Arguments
+    /// * max_size: the number of distinct keys the multiset can hold,
+    ///   every key must be smaller than it
+    ///
+    /// # Returns
+    /// * An empty multiset
+    ///
+    /// # Panics
+    /// * If max_size is larger than the number of values the key
+    ///   type can represent
+    pub fn new(max_size: usize) -> Self {
+        VebMultiset {
+            counts: VEBMap::new(max_size),
+            len: 0,
+        }
+    }
+
+    /// Creates a new empty VebMultiset with given max capacity that
+    ///     only allocates clusters as keys are inserted into them.
+    ///
+    /// # Arguments
+    /// * max_size: the number of distinct keys the multiset can hold,
+    ///   every key must be smaller than it
+    ///
+    /// # Returns
+    /// * An empty multiset
+    ///
+    /// # Panics
+    /// * If max_size is larger than the number of values the key
+    ///   type can represent
+    pub fn new_sparse(max_size: usize) -> Self {
+        VebMultiset {
+            counts: VEBMap::new_sparse(max_size),
+            len: 0,
+        }
+    }
+
+    /// Adds one copy of a key to the multiset
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    /// * key: the key to add
+    ///
+    /// # Returns
+    /// * The number of copies of 'key' after the insert
+    ///
+    /// # Panics
+    /// * If the key is outside of the universe, see try_insert
+    pub fn insert(&mut self, key: K) -> usize {
+        match self.try_insert(key) {
+            Ok(count) => count,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Adds one copy of a key to the multiset, rejecting keys outside
+    ///     of the universe.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    /// * key: the key to add
+    ///
+    /// # Returns
+    /// * The number of copies of 'key' after the insert, or
+    ///   VebError::OutOfRange if it is outside of the universe
+    pub fn try_insert(&mut self, key: K) -> Result<usize, VebError<K>> {
+        check_key(key, self.counts.max_key())?;
+        let count = match self.counts.get_mut(key) {
+            Some(count) => {
+                *count += 1;
+                *count
+            }
+            None => {
+                self.counts.insert(key, 1);
+                1
+            }
+        };
+        self.len += 1;
+        Ok(count)
+    }
+
+    /// Removes a single copy of a key from the multiset
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    /// * key: the key to remove a copy of
+    ///
+    /// # Returns
+    /// * true if a copy was removed, false if the key was not present
+    pub fn remove_one(&mut self, key: K) -> bool {
+        let last_copy = match self.counts.get_mut(key) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                false
+            }
+            Some(_) => true,
+            None => return false,
+        };
+        if last_copy {
+            self.counts.remove(key);
+        }
+        self.len -= 1;
+        true
+    }
+
+    /// Removes every copy of a key from the multiset
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    /// * key: the key to remove
+    ///
+    /// # Returns
+    /// * The number of copies that were removed, 0 if the key was not
+    ///   present
+    pub fn remove_all(&mut self, key: K) -> usize {
+        let count = self.counts.remove(key).unwrap_or(0);
+        self.len -= count;
+        count
+    }
+
+    /// Gets the number of copies of a key in the multiset
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    /// * key: the key to count
+    ///
+    /// # Returns
+    /// * The number of copies of 'key', 0 if it is not present
+    pub fn count(&self, key: K) -> usize {
+        self.counts.get(key).cloned().unwrap_or(0)
+    }
+
+    /// Returns whether at least one copy of a key is in the multiset
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset
+    /// * key: the key for which to check membership
+    ///
+    /// # Returns
+    /// * Whether or not the key is contained in the multiset
+    pub fn contains(&self, key: K) -> bool {
+        self.counts.contains_key(key)
+    }
+
+    /// Gets the number of keys the multiset can hold, every key must
+    ///     be smaller than this.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    ///
+    /// # Returns
+    /// * The max_size the multiset was created with
+    pub fn universe(&self) -> usize {
+        self.counts.universe()
+    }
+
+    /// Gets the total number of copies in the multiset, in constant
+    ///     time
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    ///
+    /// # Returns
+    /// * The sum of the counts of every key
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Gets the number of distinct keys in the multiset, in constant
+    ///     time
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    ///
+    /// # Returns
+    /// * The number of keys with a count of at least one
+    pub fn distinct_len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Returns whether the multiset holds no keys
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    ///
+    /// # Returns
+    /// * Whether the multiset is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the smallest key in the multiset
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    ///
+    /// # Returns
+    /// * The minimum key, None if the multiset is empty
+    pub fn minimum(&self) -> Option<K> {
+        self.counts.minimum().map(|(key, _)| key)
+    }
+
+    /// Gets the largest key in the multiset
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    ///
+    /// # Returns
+    /// * The maximum key, None if the multiset is empty
+    pub fn maximum(&self) -> Option<K> {
+        self.counts.maximum().map(|(key, _)| key)
+    }
+
+    /// Finds the next larger distinct key in the multiset, ignoring
+    ///     how many copies of 'key' there are
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    /// * key: the key to find the successor of
+    ///
+    /// # Returns
+    /// * The successor of 'key' or None if not found
+    pub fn findnext(&self, key: K) -> Option<K> {
+        self.counts.findnext(key).map(|(key, _)| key)
+    }
+
+    /// Finds the next smaller distinct key in the multiset, ignoring
+    ///     how many copies of 'key' there are
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebMultiset to operate on
+    /// * key: the key to find the predecessor of
+    ///
+    /// # Returns
+    /// * The predecessor of 'key' or None if not found
+    pub fn findprev(&self, key: K) -> Option<K> {
+        self.counts.findprev(key).map(|(key, _)| key)
+    }
+}
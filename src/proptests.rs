@@ -0,0 +1,162 @@
+use VEBTree;
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+/// One step of a differential run, applied to both a VEBTree and a
+///     BTreeSet holding the same keys.
+///
+/// Inserted and deleted keys are always inside the universe, the
+///     queries may also be just outside of it.
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Insert(u32),
+    Delete(u32),
+    Contains(u32),
+    Search(u32),
+    FindNext(u32),
+    FindPrev(u32),
+    Min,
+    Max,
+}
+
+/// Universes small enough to run dense, a mix of powers of two, their
+///     neighbours and sizes that are neither squares nor powers
+fn universe() -> impl Strategy<Value = usize> {
+    prop_oneof![
+        1usize..=200,
+        select(vec![
+            255usize, 256, 257, 1000, 1023, 1024, 1025, 4095, 4096, 4097, 10_000, 65_537,
+        ]),
+    ]
+}
+
+/// Keys biased towards the bottom of the universe, so that runs touch
+///     the same few clusters often, with the rest spread over it and
+///     a little past its end
+fn key(universe: usize) -> impl Strategy<Value = u32> {
+    let universe = universe as u32;
+    prop_oneof![
+        2 => 0..universe.min(80),
+        2 => 0..universe,
+        1 => universe..universe + 3,
+    ]
+}
+
+fn op(universe: usize) -> impl Strategy<Value = Op> {
+    let universe = universe as u32;
+    // Updates wrap their key into the universe, insert would panic
+    // on it otherwise
+    let update = move |key: u32| key % universe;
+    prop_oneof![
+        4 => key(universe as usize).prop_map(move |key| Op::Insert(update(key))),
+        2 => key(universe as usize).prop_map(move |key| Op::Delete(update(key))),
+        1 => key(universe as usize).prop_map(Op::Contains),
+        1 => key(universe as usize).prop_map(Op::Search),
+        2 => key(universe as usize).prop_map(Op::FindNext),
+        2 => key(universe as usize).prop_map(Op::FindPrev),
+        1 => Just(Op::Min),
+        1 => Just(Op::Max),
+    ]
+}
+
+/// A universe, a layout and the operations to run against it
+fn case(max_ops: usize) -> impl Strategy<Value = (usize, bool, Vec<Op>)> {
+    (universe(), any::<bool>()).prop_flat_map(move |(universe, sparse)| {
+        (Just(universe), Just(sparse), vec(op(universe), 0..max_ops))
+    })
+}
+
+/// Runs a sequence of operations against a tree and a BTreeSet,
+///     failing at the first step where they disagree
+fn check_ops(test_tree: &mut VEBTree<u32>, ops: &[Op]) -> Result<(), TestCaseError> {
+    let mut reference = BTreeSet::new();
+    for (step, &op) in ops.iter().enumerate() {
+        match op {
+            Op::Insert(key) => {
+                prop_assert_eq!(test_tree.insert(key), reference.insert(key), "step {}", step)
+            }
+            Op::Delete(key) => {
+                prop_assert_eq!(test_tree.delete(key), reference.remove(&key), "step {}", step)
+            }
+            Op::Contains(key) => {
+                prop_assert_eq!(test_tree.contains(key), reference.contains(&key), "step {}", step)
+            }
+            Op::Search(key) => prop_assert_eq!(
+                test_tree.search(key),
+                reference.get(&key).cloned(),
+                "step {}",
+                step
+            ),
+            Op::FindNext(key) => prop_assert_eq!(
+                test_tree.findnext(key),
+                reference
+                    .range((Bound::Excluded(key), Bound::Unbounded))
+                    .next()
+                    .cloned(),
+                "step {}",
+                step
+            ),
+            Op::FindPrev(key) => prop_assert_eq!(
+                test_tree.findprev(key),
+                reference.range(..key).next_back().cloned(),
+                "step {}",
+                step
+            ),
+            Op::Min => {
+                prop_assert_eq!(test_tree.minimum(), reference.iter().next().cloned(), "step {}", step)
+            }
+            Op::Max => prop_assert_eq!(
+                test_tree.maximum(),
+                reference.iter().next_back().cloned(),
+                "step {}",
+                step
+            ),
+        }
+        prop_assert_eq!(test_tree.len(), reference.len(), "step {}", step);
+    }
+    prop_assert_eq!(test_tree.validate(), Ok(()));
+    prop_assert_eq!(
+        test_tree.iter().collect::<Vec<u32>>(),
+        reference.into_iter().collect::<Vec<u32>>()
+    );
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn prop_matches_btreeset((universe, sparse, ops) in case(200)) {
+        let mut test_tree = if sparse {
+            VEBTree::new_sparse(universe)
+        } else {
+            VEBTree::new(universe)
+        };
+        check_ops(&mut test_tree, &ops)?;
+    }
+
+    // Sparse trees can cover the whole key type, so long runs of
+    // updates are checked against universes far too large to allocate.
+    // Keys come from a small pool so that deletes find them again.
+    #[test]
+    fn prop_sparse_wide_universe_matches_btreeset(
+        universe in select(vec![1usize << 20, (1 << 31) + 12_345, 1 << 32]),
+        pool in vec(any::<u32>(), 1..40),
+        steps in vec((any::<usize>(), any::<bool>()), 0..300),
+    ) {
+        let mut test_tree = VEBTree::new_sparse(universe);
+        let ops: Vec<Op> = steps
+            .into_iter()
+            .flat_map(|(index, insert)| {
+                let key = (u64::from(pool[index % pool.len()]) % universe as u64) as u32;
+                let update = if insert { Op::Insert(key) } else { Op::Delete(key) };
+                vec![update, Op::FindNext(key), Op::FindPrev(key.wrapping_add(1)), Op::Min, Op::Max]
+            })
+            .collect();
+        check_ops(&mut test_tree, &ops)?;
+    }
+}
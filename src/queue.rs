@@ -0,0 +1,233 @@
+use std::collections::VecDeque;
+
+use error::{check_key, VebError};
+use key::VEBKey;
+use map::VEBMap;
+
+/// A priority queue over integer priorities, backed by a VEBMap
+///
+/// Any number of items can share a priority.  They are kept in one
+///     bucket per priority and leave it in the order they were
+///     pushed, from either end of the queue, so the tree itself only
+///     ever holds the distinct priorities.  This suits monotone uses
+///     such as Dijkstra over integer distances, where pop_min is a
+///     constant time bucket pop for all but the last item of a
+///     priority.
+///
+/// # Fields
+/// * buckets: `VEBMap<K, VecDeque<T>>` - the items waiting at each
+///   priority, never holding an empty bucket
+/// * len: usize - the total number of items in all of the buckets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VebPriorityQueue<K: VEBKey, T> {
+    buckets: VEBMap<K, VecDeque<T>>,
+    len: usize,
+}
+
+impl<K: VEBKey, T> VebPriorityQueue<K, T> {
+    /// Creates a new empty queue for priorities smaller than max_size
+    ///
+    /// # Arguments
+    /// * max_size: the number of distinct priorities, every priority
+    ///   must be smaller than it
+    ///
+    /// # Returns
+    /// * An empty queue
+    ///
+    /// # Panics
+    /// * If max_size is larger than the number of values the key
+    ///   type can represent
+    pub fn new(max_size: usize) -> Self {
+        VebPriorityQueue {
+            buckets: VEBMap::new(max_size),
+            len: 0,
+        }
+    }
+
+    /// Creates a new empty queue for priorities smaller than max_size
+    ///     that only allocates clusters as priorities are used.
+    ///
+    /// # Arguments
+    /// * max_size: the number of distinct priorities, every priority
+    ///   must be smaller than it
+    ///
+    /// # Returns
+    /// * An empty queue
+    ///
+    /// # Panics
+    /// * If max_size is larger than the number of values the key
+    ///   type can represent
+    pub fn new_sparse(max_size: usize) -> Self {
+        VebPriorityQueue {
+            buckets: VEBMap::new_sparse(max_size),
+            len: 0,
+        }
+    }
+
+    /// Adds an item to the queue at the given priority, behind any
+    ///     items already waiting there.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebPriorityQueue to operate on
+    /// * priority: the priority of the item
+    /// * item: the item to add
+    ///
+    /// # Panics
+    /// * If the priority is outside of the universe, see try_push
+    pub fn push(&mut self, priority: K, item: T) {
+        if let Err(err) = self.try_push(priority, item) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Adds an item to the queue at the given priority, rejecting
+    ///     priorities outside of the universe.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebPriorityQueue to operate on
+    /// * priority: the priority of the item
+    /// * item: the item to add
+    ///
+    /// # Returns
+    /// * Ok once the item is queued, VebError::OutOfRange if the
+    ///   priority is outside of the universe
+    pub fn try_push(&mut self, priority: K, item: T) -> Result<(), VebError<K>> {
+        check_key(priority, self.buckets.max_key())?;
+        match self.buckets.get_mut(priority) {
+            Some(bucket) => bucket.push_back(item),
+            None => {
+                let mut bucket = VecDeque::with_capacity(1);
+                bucket.push_back(item);
+                self.buckets.insert(priority, bucket);
+            }
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes the first item pushed at the lowest priority
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebPriorityQueue to operate on
+    ///
+    /// # Returns
+    /// * The priority and the item, None if the queue is empty
+    pub fn pop_min(&mut self) -> Option<(K, T)> {
+        let (priority, waiting) = self
+            .buckets
+            .minimum()
+            .map(|(priority, bucket)| (priority, bucket.len()))?;
+        let item = if waiting == 1 {
+            self.buckets
+                .pop_min()
+                .and_then(|(_, mut bucket)| bucket.pop_front())
+        } else {
+            self.buckets
+                .get_mut(priority)
+                .and_then(|bucket| bucket.pop_front())
+        }
+        .expect("Data structure appears corrupt");
+        self.len -= 1;
+        Some((priority, item))
+    }
+
+    /// Removes the first item pushed at the highest priority
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebPriorityQueue to operate on
+    ///
+    /// # Returns
+    /// * The priority and the item, None if the queue is empty
+    pub fn pop_max(&mut self) -> Option<(K, T)> {
+        let (priority, waiting) = self
+            .buckets
+            .maximum()
+            .map(|(priority, bucket)| (priority, bucket.len()))?;
+        let item = if waiting == 1 {
+            self.buckets
+                .pop_max()
+                .and_then(|(_, mut bucket)| bucket.pop_front())
+        } else {
+            self.buckets
+                .get_mut(priority)
+                .and_then(|bucket| bucket.pop_front())
+        }
+        .expect("Data structure appears corrupt");
+        self.len -= 1;
+        Some((priority, item))
+    }
+
+    /// Gets the item pop_min would remove, without removing it
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebPriorityQueue to operate on
+    ///
+    /// # Returns
+    /// * The lowest priority and the first item pushed at it, None
+    ///   if the queue is empty
+    pub fn peek_min(&self) -> Option<(K, &T)> {
+        let (priority, bucket) = self.buckets.minimum()?;
+        Some((priority, bucket.front()?))
+    }
+
+    /// Gets the item pop_max would remove, without removing it
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebPriorityQueue to operate on
+    ///
+    /// # Returns
+    /// * The highest priority and the first item pushed at it, None
+    ///   if the queue is empty
+    pub fn peek_max(&self) -> Option<(K, &T)> {
+        let (priority, bucket) = self.buckets.maximum()?;
+        Some((priority, bucket.front()?))
+    }
+
+    /// Gets the number of items waiting at a priority
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebPriorityQueue to operate on
+    /// * priority: the priority to look at
+    ///
+    /// # Returns
+    /// * The number of items at 'priority', 0 for priorities outside
+    ///   of the universe
+    pub fn count(&self, priority: K) -> usize {
+        self.buckets.get(priority).map_or(0, |bucket| bucket.len())
+    }
+
+    /// Gets the number of priorities the queue can hold, every
+    ///     priority must be smaller than this.
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebPriorityQueue to operate on
+    ///
+    /// # Returns
+    /// * The max_size the queue was created with
+    pub fn universe(&self) -> usize {
+        self.buckets.universe()
+    }
+
+    /// Gets the number of items in the queue, counting every item of
+    ///     a shared priority
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebPriorityQueue to operate on
+    ///
+    /// # Returns
+    /// * The number of items in the queue
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the queue holds no items
+    ///
+    /// # Arguments
+    /// * self: the instance of the VebPriorityQueue to operate on
+    ///
+    /// # Returns
+    /// * Whether the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
@@ -0,0 +1,128 @@
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use bulk::sorted_keys;
+use error::check_key;
+use key::VEBKey;
+use VEBTree;
+
+/// The serialized form of a VEBTree.  Only the elements are written,
+///     the recursive structure is rebuilt on load.
+///
+/// # Fields
+/// * max_key: the largest key the tree can hold, None for an empty
+///   universe.  Kept in the key type, a count of keys would not fit
+///   for trees that cover all of it.
+/// * sparse: whether the tree was created with new_sparse
+/// * contents: the elements of the tree
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "VEBTree")]
+struct Snapshot<K> {
+    max_key: Option<K>,
+    sparse: bool,
+    contents: Contents<K>,
+}
+
+/// The elements of a serialized VEBTree, in whichever form is shorter
+///
+/// # Variants
+/// * Keys: every element in ascending order
+/// * Runs: (first, last) pairs of consecutive elements in ascending
+///   order, both ends included
+#[derive(Serialize, Deserialize)]
+enum Contents<K> {
+    Keys(Vec<K>),
+    Runs(Vec<(K, K)>),
+}
+
+impl<K: VEBKey> Contents<K> {
+    /// Encodes the elements of a tree as runs if that halves the
+    ///     number of values written, as a plain list otherwise
+    fn from_tree(tree: &VEBTree<K>) -> Self {
+        let mut runs: Vec<(K, K)> = Vec::new();
+        for value in tree {
+            match runs.last_mut() {
+                Some(run) if run.1 + K::ONE == value => run.1 = value,
+                _ => runs.push((value, value)),
+            }
+        }
+        if runs.len() * 2 < tree.len() {
+            Contents::Runs(runs)
+        } else {
+            Contents::Keys(tree.iter().collect())
+        }
+    }
+}
+
+impl<K: VEBKey + Serialize> Serialize for VEBTree<K> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Snapshot {
+            max_key: self.max_key(),
+            sparse: self.is_sparse(),
+            contents: Contents::from_tree(self),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<K: VEBKey> Contents<K> {
+    /// Expands the elements into strictly ascending keys, checking
+    ///     that runs are in order and reserving room for all of them
+    ///     up front, so that a few runs covering billions of keys
+    ///     fail instead of aborting on allocation
+    fn into_keys(self) -> Result<Vec<K>, String> {
+        let runs = match self {
+            Contents::Keys(keys) => return Ok(sorted_keys(keys)),
+            Contents::Runs(runs) => runs,
+        };
+        let mut total: usize = 0;
+        let mut previous: Option<K> = None;
+        for &(first, last) in &runs {
+            if first > last {
+                return Err(format!("run {:?}..={:?} is backwards", first, last));
+            }
+            if previous.is_some_and(|previous| first <= previous) {
+                return Err(format!(
+                    "run {:?}..={:?} overlaps the one before it",
+                    first, last
+                ));
+            }
+            previous = Some(last);
+            total = total.saturating_add((last - first).to_usize().saturating_add(1));
+        }
+        let mut keys = Vec::new();
+        if keys.try_reserve_exact(total).is_err() {
+            return Err(format!(
+                "runs cover {} keys, more than can be held in memory",
+                total
+            ));
+        }
+        for (first, last) in runs {
+            let mut value = first;
+            keys.push(value);
+            while value < last {
+                value = value + K::ONE;
+                keys.push(value);
+            }
+        }
+        Ok(keys)
+    }
+}
+
+impl<'de, K: VEBKey + Deserialize<'de>> Deserialize<'de> for VEBTree<K> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let snapshot = Snapshot::<K>::deserialize(deserializer)?;
+        // Checking the largest key first bounds what the runs expand
+        // to by the universe
+        let last = match snapshot.contents {
+            Contents::Keys(ref keys) => keys.iter().max().cloned(),
+            Contents::Runs(ref runs) => runs.iter().map(|&(_, last)| last).max(),
+        };
+        if let Some(last) = last {
+            check_key(last, snapshot.max_key).map_err(D::Error::custom)?;
+        }
+        let keys = snapshot.contents.into_keys().map_err(D::Error::custom)?;
+        let sparse = VEBTree::loaded_sparse(snapshot.max_key, snapshot.sparse);
+        Ok(VEBTree::from_sorted_keys(snapshot.max_key, sparse, keys))
+    }
+}
@@ -0,0 +1,385 @@
+use std::cmp::Ordering;
+use std::iter::FusedIterator;
+
+use key::VEBKey;
+use VEBTree;
+
+/// A lazy iterator over the union of two VEBTrees in ascending order
+///
+/// Created by VEBTree::union.
+///
+/// # Fields
+/// * a, b: the trees being combined
+/// * next_a, next_b: the next element of each tree not yet yielded
+#[derive(Clone, Debug)]
+pub struct Union<'a, K: VEBKey + 'a> {
+    a: &'a VEBTree<K>,
+    b: &'a VEBTree<K>,
+    next_a: Option<K>,
+    next_b: Option<K>,
+}
+
+impl<'a, K: VEBKey> Iterator for Union<'a, K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        let ordering = match (self.next_a, self.next_b) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+        let current = if ordering == Ordering::Greater {
+            self.next_b
+        } else {
+            self.next_a
+        }?;
+        if ordering != Ordering::Greater {
+            self.next_a = self.a.findnext(current);
+        }
+        if ordering != Ordering::Less {
+            self.next_b = self.b.findnext(current);
+        }
+        Some(current)
+    }
+}
+
+impl<'a, K: VEBKey> FusedIterator for Union<'a, K> {}
+
+/// A lazy iterator over the intersection of two VEBTrees in ascending
+///     order
+///
+/// Created by VEBTree::intersection.  Each tree jumps straight to the
+///     next candidate from the other, so long runs that only appear
+///     in one of them are skipped over.
+///
+/// # Fields
+/// * a, b: the trees being intersected
+/// * next_a: the next element of a that may be in b
+#[derive(Clone, Debug)]
+pub struct Intersection<'a, K: VEBKey + 'a> {
+    a: &'a VEBTree<K>,
+    b: &'a VEBTree<K>,
+    next_a: Option<K>,
+}
+
+impl<'a, K: VEBKey> Iterator for Intersection<'a, K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        loop {
+            let candidate = self.next_a?;
+            let in_b = self.b.ceil(candidate);
+            if in_b == Some(candidate) {
+                self.next_a = self.a.findnext(candidate);
+                return Some(candidate);
+            }
+            self.next_a = match in_b {
+                Some(value) => self.a.ceil(value),
+                None => None,
+            };
+        }
+    }
+}
+
+impl<'a, K: VEBKey> FusedIterator for Intersection<'a, K> {}
+
+/// A lazy iterator over the elements of one VEBTree that are not in
+///     another, in ascending order
+///
+/// Created by VEBTree::difference.
+///
+/// # Fields
+/// * a: the tree whose elements are yielded
+/// * b: the tree whose elements are skipped
+/// * next_a: the next element of a not yet considered
+/// * next_b: the smallest element of b that is at least next_a
+#[derive(Clone, Debug)]
+pub struct Difference<'a, K: VEBKey + 'a> {
+    a: &'a VEBTree<K>,
+    b: &'a VEBTree<K>,
+    next_a: Option<K>,
+    next_b: Option<K>,
+}
+
+impl<'a, K: VEBKey> Iterator for Difference<'a, K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        loop {
+            let candidate = self.next_a?;
+            self.next_a = self.a.findnext(candidate);
+            if self.next_b.is_some_and(|value| value < candidate) {
+                self.next_b = self.b.ceil(candidate);
+            }
+            if self.next_b != Some(candidate) {
+                return Some(candidate);
+            }
+        }
+    }
+}
+
+impl<'a, K: VEBKey> FusedIterator for Difference<'a, K> {}
+
+/// A lazy iterator over the elements that are in exactly one of two
+///     VEBTrees, in ascending order
+///
+/// Created by VEBTree::symmetric_difference.
+///
+/// # Fields
+/// * a, b: the trees being compared
+/// * next_a, next_b: the next element of each tree not yet considered
+#[derive(Clone, Debug)]
+pub struct SymmetricDifference<'a, K: VEBKey + 'a> {
+    a: &'a VEBTree<K>,
+    b: &'a VEBTree<K>,
+    next_a: Option<K>,
+    next_b: Option<K>,
+}
+
+impl<'a, K: VEBKey> Iterator for SymmetricDifference<'a, K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        loop {
+            match (self.next_a, self.next_b) {
+                (Some(a), Some(b)) if a == b => {
+                    self.next_a = self.a.findnext(a);
+                    self.next_b = self.b.findnext(b);
+                }
+                (Some(a), Some(b)) if a < b => {
+                    self.next_a = self.a.findnext(a);
+                    return Some(a);
+                }
+                (Some(a), None) => {
+                    self.next_a = self.a.findnext(a);
+                    return Some(a);
+                }
+                (_, Some(b)) => {
+                    self.next_b = self.b.findnext(b);
+                    return Some(b);
+                }
+                (None, None) => return None,
+            }
+        }
+    }
+}
+
+impl<'a, K: VEBKey> FusedIterator for SymmetricDifference<'a, K> {}
+
+impl<K: VEBKey> VEBTree<K> {
+    /// Panics unless both trees were created with the same universe
+    fn check_same_universe(&self, other: &VEBTree<K>) {
+        assert_eq!(
+            self.max_key, other.max_key,
+            "set operations need trees of the same universe"
+        );
+    }
+
+    /// Returns a lazy iterator over the elements that are in either
+    ///     tree, in ascending order.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * other: the tree to combine with
+    ///
+    /// # Returns
+    /// * An iterator over the union of the trees
+    ///
+    /// # Panics
+    /// * If the trees have different universes
+    pub fn union<'a>(&'a self, other: &'a VEBTree<K>) -> Union<'a, K> {
+        self.check_same_universe(other);
+        Union {
+            a: self,
+            b: other,
+            next_a: self.minimum(),
+            next_b: other.minimum(),
+        }
+    }
+
+    /// Returns a lazy iterator over the elements that are in both
+    ///     trees, in ascending order.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * other: the tree to intersect with
+    ///
+    /// # Returns
+    /// * An iterator over the intersection of the trees
+    ///
+    /// # Panics
+    /// * If the trees have different universes
+    pub fn intersection<'a>(&'a self, other: &'a VEBTree<K>) -> Intersection<'a, K> {
+        self.check_same_universe(other);
+        Intersection {
+            a: self,
+            b: other,
+            next_a: self.minimum(),
+        }
+    }
+
+    /// Returns a lazy iterator over the elements that are in this tree
+    ///     but not in other, in ascending order.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * other: the tree whose elements are left out
+    ///
+    /// # Returns
+    /// * An iterator over the difference of the trees
+    ///
+    /// # Panics
+    /// * If the trees have different universes
+    pub fn difference<'a>(&'a self, other: &'a VEBTree<K>) -> Difference<'a, K> {
+        self.check_same_universe(other);
+        Difference {
+            a: self,
+            b: other,
+            next_a: self.minimum(),
+            next_b: other.minimum(),
+        }
+    }
+
+    /// Returns a lazy iterator over the elements that are in exactly
+    ///     one of the trees, in ascending order.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * other: the tree to compare with
+    ///
+    /// # Returns
+    /// * An iterator over the symmetric difference of the trees
+    ///
+    /// # Panics
+    /// * If the trees have different universes
+    pub fn symmetric_difference<'a>(
+        &'a self,
+        other: &'a VEBTree<K>,
+    ) -> SymmetricDifference<'a, K> {
+        self.check_same_universe(other);
+        SymmetricDifference {
+            a: self,
+            b: other,
+            next_a: self.minimum(),
+            next_b: other.minimum(),
+        }
+    }
+
+    /// Adds every element of other to this tree
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to modify
+    /// * other: the tree whose elements are added
+    ///
+    /// # Panics
+    /// * If the trees have different universes
+    pub fn union_with(&mut self, other: &VEBTree<K>) {
+        self.check_same_universe(other);
+        for value in other {
+            self.insert(value);
+        }
+    }
+
+    /// Removes every element of this tree that is not in other
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to modify
+    /// * other: the tree whose elements are kept
+    ///
+    /// # Panics
+    /// * If the trees have different universes
+    pub fn intersection_with(&mut self, other: &VEBTree<K>) {
+        self.check_same_universe(other);
+        let mut current = self.minimum();
+        while let Some(value) = current {
+            let next = self.findnext(value);
+            if !other.contains(value) {
+                self.delete(value);
+            }
+            current = next;
+        }
+    }
+
+    /// Removes every element of other from this tree
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to modify
+    /// * other: the tree whose elements are removed
+    ///
+    /// # Panics
+    /// * If the trees have different universes
+    pub fn difference_with(&mut self, other: &VEBTree<K>) {
+        self.check_same_universe(other);
+        let (first, last) = match (self.minimum(), self.maximum()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return,
+        };
+        for value in other.range(first..=last) {
+            self.delete(value);
+        }
+    }
+
+    /// Keeps only the elements that are in exactly one of the trees
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to modify
+    /// * other: the tree to compare with
+    ///
+    /// # Panics
+    /// * If the trees have different universes
+    pub fn symmetric_difference_with(&mut self, other: &VEBTree<K>) {
+        self.check_same_universe(other);
+        for value in other {
+            if !self.delete(value) {
+                self.insert(value);
+            }
+        }
+    }
+
+    /// Returns whether every element of this tree is also in other
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * other: the tree to compare with
+    ///
+    /// # Returns
+    /// * Whether this tree is a subset of other
+    ///
+    /// # Panics
+    /// * If the trees have different universes
+    pub fn is_subset(&self, other: &VEBTree<K>) -> bool {
+        self.check_same_universe(other);
+        self.len() <= other.len() && self.iter().all(|value| other.contains(value))
+    }
+
+    /// Returns whether every element of other is also in this tree
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * other: the tree to compare with
+    ///
+    /// # Returns
+    /// * Whether this tree is a superset of other
+    ///
+    /// # Panics
+    /// * If the trees have different universes
+    pub fn is_superset(&self, other: &VEBTree<K>) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns whether the trees have no elements in common
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to operate on
+    /// * other: the tree to compare with
+    ///
+    /// # Returns
+    /// * Whether the intersection of the trees is empty
+    ///
+    /// # Panics
+    /// * If the trees have different universes
+    pub fn is_disjoint(&self, other: &VEBTree<K>) -> bool {
+        self.intersection(other).next().is_none()
+    }
+}
@@ -0,0 +1,332 @@
+use std::io::{self, Read, Write};
+
+use error::SnapshotError;
+use key::{universe_size, VEBKey};
+use VEBTree;
+
+// Snapshot layout, all integers little endian:
+//
+//   magic       4 bytes   "VEBT"
+//   version     u16       FORMAT_VERSION
+//   key width   u8        K::BITS of the tree that was written
+//   flags       u8        bit 0 set for sparse trees, bit 1 set when
+//                         the universe is empty
+//   max key     varint    the largest key of the universe, 0 when it
+//                         is empty
+//   len         u64       number of elements
+//   encoding    u8        ENCODING_DELTA or ENCODING_BITMAP
+//   payload     u64 length followed by that many bytes
+//   checksum    u32       CRC-32 of every byte before it
+//
+// The delta encoding is a LEB128 varint per element, the first
+//     element as is and every later one as the gap to the previous
+//     element minus one.  The bitmap encoding is one bit per key of
+//     the universe, least significant bit first.  Whichever is
+//     smaller is written.
+//
+// Readers must keep accepting every version that has been released,
+//     new versions get a new number rather than changing this one.
+
+const MAGIC: &[u8; 4] = b"VEBT";
+const FORMAT_VERSION: u16 = 1;
+const FLAG_SPARSE: u8 = 1;
+const FLAG_EMPTY_UNIVERSE: u8 = 2;
+const ENCODING_DELTA: u8 = 0;
+const ENCODING_BITMAP: u8 = 1;
+
+/// Lookup table for the reflected CRC-32 (IEEE 802.3) polynomial
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// A running CRC-32 over the bytes passed to update
+struct Crc32(u32);
+
+impl Crc32 {
+    fn new() -> Self {
+        Crc32(0xFFFF_FFFF)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = CRC_TABLE[((self.0 ^ u32::from(byte)) & 0xFF) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+/// Wraps a reader or writer, keeping a checksum of the bytes that
+///     pass through it
+struct Checksummed<T> {
+    inner: T,
+    crc: Crc32,
+}
+
+impl<T> Checksummed<T> {
+    fn new(inner: T) -> Self {
+        Checksummed {
+            inner,
+            crc: Crc32::new(),
+        }
+    }
+}
+
+impl<W: Write> Checksummed<W> {
+    fn put(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.crc.update(bytes);
+        self.inner.write_all(bytes)
+    }
+}
+
+impl<R: Read> Checksummed<R> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        let mut bytes = [0u8; N];
+        self.inner.read_exact(&mut bytes)?;
+        self.crc.update(&bytes);
+        Ok(bytes)
+    }
+
+    fn take_u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn take_varint<K: VEBKey>(&mut self) -> Result<K, SnapshotError> {
+        decode_varint(|| Ok(self.take::<1>()?[0]))
+    }
+}
+
+/// Appends a key to a buffer as a LEB128 varint
+fn push_varint<K: VEBKey>(buffer: &mut Vec<u8>, mut value: K) {
+    let low = K::from_usize(0x7F);
+    while value > low {
+        buffer.push((value & low).to_usize() as u8 | 0x80);
+        value = value >> 7;
+    }
+    buffer.push(value.to_usize() as u8);
+}
+
+/// Decodes a LEB128 varint into a key, one byte at a time
+///
+/// # Arguments
+/// * next_byte: returns the next byte of the varint
+///
+/// # Returns
+/// * The decoded key, or SnapshotError::Corrupt if it does not fit in
+///   the key type
+fn decode_varint<K: VEBKey, F>(mut next_byte: F) -> Result<K, SnapshotError>
+where
+    F: FnMut() -> Result<u8, SnapshotError>,
+{
+    let mut value = K::ZERO;
+    let mut shift = 0;
+    loop {
+        let byte = next_byte()?;
+        let bits = K::from_usize(usize::from(byte & 0x7F));
+        // Every group must land inside the key rather than be shifted
+        // out of it
+        if shift >= K::BITS || (bits << shift) >> shift != bits {
+            return Err(SnapshotError::Corrupt("element does not fit in the key type"));
+        }
+        value = value + (bits << shift);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Reads a LEB128 varint from the front of a buffer, advancing it
+fn pop_varint<K: VEBKey>(buffer: &mut &[u8]) -> Result<K, SnapshotError> {
+    decode_varint(|| {
+        let (&byte, rest) = buffer
+            .split_first()
+            .ok_or(SnapshotError::Corrupt("payload ends inside an element"))?;
+        *buffer = rest;
+        Ok(byte)
+    })
+}
+
+impl<K: VEBKey> VEBTree<K> {
+    /// Writes the tree to a writer in the versioned snapshot format
+    ///
+    /// The snapshot records the universe, the key width and whether
+    ///     the tree is sparse, followed by the elements and a CRC-32
+    ///     of the whole snapshot.  Snapshots written by this version
+    ///     of the crate can be read by every later version.
+    ///
+    /// # Arguments
+    /// * self: the instance of VEBTree to write
+    /// * writer: where to write the snapshot
+    ///
+    /// # Returns
+    /// * Ok once the whole snapshot has been written, or the error
+    ///   reported by the writer
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut deltas = Vec::new();
+        let mut previous: Option<K> = None;
+        for value in self {
+            push_varint(&mut deltas, previous.map_or(value, |prev| value - prev - K::ONE));
+            previous = Some(value);
+        }
+        // Saturates for universes too large for a usize, which never
+        // make the bitmap the smaller of the two
+        let bitmap_len = self.universe().div_ceil(8);
+        let (encoding, payload) = if bitmap_len < deltas.len() {
+            let mut bitmap = vec![0u8; bitmap_len];
+            for value in self {
+                let value = value.to_usize();
+                bitmap[value / 8] |= 1 << (value % 8);
+            }
+            (ENCODING_BITMAP, bitmap)
+        } else {
+            (ENCODING_DELTA, deltas)
+        };
+
+        let mut out = Checksummed::new(writer);
+        out.put(MAGIC)?;
+        out.put(&FORMAT_VERSION.to_le_bytes())?;
+        let mut flags = if self.is_sparse() { FLAG_SPARSE } else { 0 };
+        if self.max_key.is_none() {
+            flags |= FLAG_EMPTY_UNIVERSE;
+        }
+        out.put(&[K::BITS as u8, flags])?;
+        let mut max_key = Vec::new();
+        push_varint(&mut max_key, self.max_key.unwrap_or(K::ZERO));
+        out.put(&max_key)?;
+        out.put(&(self.len() as u64).to_le_bytes())?;
+        out.put(&[encoding])?;
+        out.put(&(payload.len() as u64).to_le_bytes())?;
+        out.put(&payload)?;
+        let checksum = out.crc.finish();
+        out.inner.write_all(&checksum.to_le_bytes())
+    }
+
+    /// Reads a tree from a snapshot written by write_to
+    ///
+    /// The checksum is verified before any of the elements are
+    ///     decoded, so a corrupted snapshot never produces a tree.
+    ///     The tree is then built bottom-up as in from_sorted_iter.
+    ///     Dense trees with a universe above MAX_LOADED_DENSE_UNIVERSE
+    ///     come back sparse, holding the same keys.
+    ///
+    /// # Arguments
+    /// * reader: where to read the snapshot from, it is left just
+    ///   after the end of the snapshot
+    ///
+    /// # Returns
+    /// * The tree that was written, or a SnapshotError describing why
+    ///   the input could not be read
+    pub fn read_from<R: Read>(reader: R) -> Result<VEBTree<K>, SnapshotError> {
+        let mut input = Checksummed::new(reader);
+        if &input.take::<4>()? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = u16::from_le_bytes(input.take()?);
+        if version != FORMAT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let [key_width, flags] = input.take()?;
+        if u32::from(key_width) != K::BITS {
+            return Err(SnapshotError::KeyWidthMismatch {
+                expected: K::BITS,
+                found: u32::from(key_width),
+            });
+        }
+        let max_key = input.take_varint()?;
+        let max_key: Option<K> = if flags & FLAG_EMPTY_UNIVERSE != 0 {
+            if max_key != K::ZERO {
+                return Err(SnapshotError::Corrupt("empty universe has a max key"));
+            }
+            None
+        } else {
+            Some(max_key)
+        };
+        let len = input.take_u64()?;
+        let [encoding] = input.take()?;
+        let payload_len = input.take_u64()?;
+
+        // Read through take so a corrupted length cannot make us
+        // allocate more than the input actually holds
+        let mut payload = Vec::new();
+        (&mut input.inner).take(payload_len).read_to_end(&mut payload)?;
+        if (payload.len() as u64) < payload_len {
+            return Err(SnapshotError::Truncated);
+        }
+        input.crc.update(&payload);
+        let expected = input.crc.finish();
+        let found = u32::from_le_bytes(input.take()?);
+        if found != expected {
+            return Err(SnapshotError::ChecksumMismatch { expected, found });
+        }
+
+        if flags & !(FLAG_SPARSE | FLAG_EMPTY_UNIVERSE) != 0 {
+            return Err(SnapshotError::Corrupt("unknown flags are set"));
+        }
+        let outside = "element is outside of the universe";
+        let mut keys: Vec<K> = Vec::new();
+        match encoding {
+            ENCODING_DELTA => {
+                let mut rest = &payload[..];
+                let mut previous: Option<K> = None;
+                while !rest.is_empty() {
+                    let delta: K = pop_varint(&mut rest)?;
+                    let max_key = max_key.ok_or(SnapshotError::Corrupt(outside))?;
+                    // Compared against what is left of the universe so
+                    // that nothing can overflow
+                    let value = match previous {
+                        Some(prev) if delta < max_key - prev => prev + delta + K::ONE,
+                        None if delta <= max_key => delta,
+                        _ => return Err(SnapshotError::Corrupt(outside)),
+                    };
+                    keys.push(value);
+                    previous = Some(value);
+                }
+            }
+            ENCODING_BITMAP => {
+                let universe = universe_size(max_key);
+                if payload_len != universe.div_ceil(8) as u64 {
+                    return Err(SnapshotError::Corrupt("bitmap does not match the universe"));
+                }
+                for (index, &byte) in payload.iter().enumerate() {
+                    for bit in 0..8 {
+                        if byte & (1 << bit) != 0 {
+                            let value = index * 8 + bit;
+                            if value >= universe {
+                                return Err(SnapshotError::Corrupt(outside));
+                            }
+                            keys.push(K::from_usize(value));
+                        }
+                    }
+                }
+            }
+            _ => return Err(SnapshotError::Corrupt("unknown encoding")),
+        }
+        if keys.len() as u64 != len {
+            return Err(SnapshotError::Corrupt("element count does not match"));
+        }
+        // Both encodings produce the keys in strictly ascending order
+        let sparse = VEBTree::loaded_sparse(max_key, flags & FLAG_SPARSE != 0);
+        Ok(VEBTree::from_sorted_keys(max_key, sparse, keys))
+    }
+}
@@ -0,0 +1,64 @@
+/// A breakdown of the memory a VEBTree or VEBMap uses, as reported
+///     by their stats method.
+///
+/// Clusters are the child nodes below the top of the recursion, all
+///     of them allocated up front in a dense tree and only once they
+///     hold a key in a sparse one.  The aux trees that index the
+///     populated clusters of each node are kept separately, and are
+///     counted separately here.
+///
+/// # Fields
+/// * levels: `Vec<LevelStats>` - the nodes at each depth of the
+///   recursion, the top level first
+/// * leaves: usize - the number of bitmap leaves at every level
+/// * allocated_clusters: usize - the number of nodes below the top
+///   level
+/// * populated_clusters: usize - how many of those hold at least one
+///   key.  Each node keeps its minimum to itself, so a cluster
+///   can be allocated and empty even in a non-empty tree.
+/// * aux_nodes: usize - the number of nodes in all of the aux trees
+/// * children_bytes: usize - heap bytes used by the nodes of the
+///   recursion, including the cluster maps of sparse nodes, the
+///   cluster counts kept for rank and select and the values stored
+///   in the leaves
+/// * aux_bytes: usize - heap bytes used by the aux trees
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    pub levels: Vec<LevelStats>,
+    pub leaves: usize,
+    pub allocated_clusters: usize,
+    pub populated_clusters: usize,
+    pub aux_nodes: usize,
+    pub children_bytes: usize,
+    pub aux_bytes: usize,
+}
+
+impl TreeStats {
+    /// Gets the total heap usage the report adds up to, the same
+    ///     value as heap_size_bytes
+    ///
+    /// # Arguments
+    /// * self: the report to total
+    ///
+    /// # Returns
+    /// * children_bytes + aux_bytes
+    pub fn heap_size_bytes(&self) -> usize {
+        self.children_bytes + self.aux_bytes
+    }
+}
+
+/// The nodes at one depth of the recursion
+///
+/// # Fields
+/// * universe: usize - the universe of the largest node at this
+///   depth, only the last cluster of a node can be smaller
+/// * nodes: usize - the number of nodes allocated at this depth
+/// * populated: usize - how many of those hold at least one key
+/// * leaves: usize - how many of those are bitmap leaves
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LevelStats {
+    pub universe: usize,
+    pub nodes: usize,
+    pub populated: usize,
+    pub leaves: usize,
+}
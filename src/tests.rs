@@ -1,131 +1,1540 @@
-use VEBTree;
+use {MAX_LOADED_DENSE_UNIVERSE, SnapshotError, TieBreak, VEBMap, VEBTree, VebError, VebMultiset, VebPriorityQueue};
 
-use std::time::{Instant, Duration};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
 
+// Every test is instantiated once per supported key width and per
+// layout, the universes used here are small enough to fit in a u8.
+macro_rules! width_tests {
+    ($ctor:ident; $($width:ident: $key:ty),*) => {$(
+        mod $width {
+            use VEBTree;
 
+            #[test]
+            fn test_contains_correctness(){
+                for j in 0..16 {
+                    let mut test_tree = VEBTree::<$key>::$ctor(16);
+                    for i in 0..j {
+                        test_tree.insert(i);
+                    }
+                    for i in 0..j {
+                        assert!(test_tree.contains(i));
+                    }
+                    for i in j..16 {
+                        assert!(!test_tree.contains(i));
+                    }
+                }
+            }
+
+            #[test]
+            fn test_search_correctness(){
+                for i in 0..16 {
+                    let mut test_tree = VEBTree::<$key>::$ctor(16);
+                    for j in 0..i {
+                        test_tree.insert(j);
+                    }
+                    for j in 0..i {
+                        assert_eq!(test_tree.search(j), Some(j));
+                    }
+                }
+            }
+
+            #[test]
+            fn test_insert_and_delete_correctness(){
+                let mut test_tree = VEBTree::<$key>::$ctor(16);
+                let reference_tree = VEBTree::<$key>::$ctor(16);
+                for i in 0..16 {
+                    test_tree.insert(i);
+                }
+                for i in 0..16 {
+                    test_tree.delete(i);
+                }
+                assert_eq!(test_tree, reference_tree);
+            }
+
+            #[test]
+            fn test_insert_and_delete_correctness_odd(){
+                for i in 17..32 {
+                    let mut test_tree = VEBTree::<$key>::$ctor(i as usize);
+                    let reference_tree = VEBTree::<$key>::$ctor(i as usize);
+                    for j in 0..i {
+                        test_tree.insert(j);
+                    }
+                    for j in 0..i {
+                        test_tree.delete(j);
+                    }
+                    assert_eq!(test_tree, reference_tree);
+                }
+            }
+
+            #[test]
+            fn test_minimum_correctness(){
+                let mut test_tree = VEBTree::<$key>::$ctor(16);
+                for i in (0..16).rev() {
+                    test_tree.insert(i);
+                    assert_eq!(test_tree.minimum(), Some(i));
+                }
+                for i in 0..15 {
+                    test_tree.delete(i);
+                    assert_eq!(test_tree.minimum(), Some(i+1));
+                }
+                for i in 0..16 {
+                    test_tree.insert(i);
+                }
+                for i in 0..16 {
+                    if i % 2 == 0 {
+                        test_tree.delete(i);
+                    } else {
+                        assert_eq!(test_tree.minimum(), Some(i));
+                        test_tree.delete(i);
+                    }
+                }
+            }
+
+            #[test]
+            fn test_maximum_correctness(){
+                let mut test_tree = VEBTree::<$key>::$ctor(16);
+                for i in 0..16 {
+                    test_tree.insert(i);
+                    assert_eq!(test_tree.maximum(), Some(i));
+                }
+                for i in (0..16).rev() {
+                    assert_eq!(test_tree.maximum(), Some(i));
+                    test_tree.delete(i);
+                }
+            }
+
+            #[test]
+            fn test_findnext_correctness(){
+                let mut test_tree = VEBTree::<$key>::$ctor(16);
+                for i in 0..16 {
+                    if i % 3 == 0 {
+                        test_tree.insert(i);
+                    }
+                }
+                for i in 0..13 {
+                    if i % 3 == 0 {
+                        assert_eq!(test_tree.findnext(i), Some(i+3));
+                    }
+                }
+            }
+
+            #[test]
+            fn test_findprev_correctness(){
+                let mut test_tree = VEBTree::<$key>::$ctor(16);
+                for i in 0..16 {
+                    if i % 3 == 0 {
+                        test_tree.insert(i);
+                    }
+                }
+                for i in 3..16 {
+                    if i % 3 == 0 {
+                        assert_eq!(test_tree.findprev(i), Some(i-3));
+                    }
+                }
+            }
+        }
+    )*};
+}
+
+mod dense {
+    width_tests!(
+        new;
+        width_u8: u8,
+        width_u16: u16,
+        width_u32: u32,
+        width_u64: u64,
+        width_u128: u128,
+        width_usize: usize
+    );
+}
+
+mod sparse {
+    width_tests!(
+        new_sparse;
+        width_u8: u8,
+        width_u16: u16,
+        width_u32: u32,
+        width_u64: u64,
+        width_u128: u128,
+        width_usize: usize
+    );
+}
 
 #[test]
-fn test_contains_correctness(){
-    for j in 0..16 {
-        let mut test_tree = VEBTree::new(16);
-        for i in 0..j {
-            test_tree.insert(i);
+fn test_default_key_is_usize(){
+    let mut test_tree: VEBTree = VEBTree::new(16);
+    test_tree.insert(3_usize);
+    assert_eq!(test_tree.minimum(), Some(3_usize));
+}
+
+#[test]
+fn test_full_width_u8_universe(){
+    let mut test_tree = VEBTree::<u8>::new(256);
+    test_tree.insert(255);
+    test_tree.insert(0);
+    assert!(test_tree.contains(255));
+    assert_eq!(test_tree.findnext(0), Some(255));
+    assert_eq!(test_tree.findprev(255), Some(0));
+}
+
+#[test]
+#[should_panic]
+fn test_universe_too_large_for_key(){
+    VEBTree::<u8>::new(257);
+}
+
+#[test]
+fn test_delete_absent_value(){
+    let mut test_tree = VEBTree::<u8>::new(16);
+    test_tree.insert(4);
+    test_tree.delete(5);
+    test_tree.delete(12);
+    assert_eq!(test_tree.minimum(), Some(4));
+    assert_eq!(test_tree.maximum(), Some(4));
+    assert!(test_tree.contains(4));
+}
+
+#[test]
+fn test_map_insert_get_remove(){
+    let mut test_map = VEBMap::<u16, String>::new(100);
+    for i in 0..100 {
+        if i % 7 == 0 {
+            assert_eq!(test_map.insert(i, i.to_string()), None);
         }
-        for i in 0..j {
-            assert!(test_tree.contains(i));
+    }
+    assert_eq!(test_map.insert(14, "fourteen".to_string()), Some("14".to_string()));
+    for i in 0..100 {
+        if i == 14 {
+            assert_eq!(test_map.get(i).map(|v| v.as_str()), Some("fourteen"));
+        } else if i % 7 == 0 {
+            assert_eq!(test_map.get(i), Some(&i.to_string()));
+        } else {
+            assert_eq!(test_map.get(i), None);
+            assert!(!test_map.contains_key(i));
         }
-        for i in j..16 {
-            assert!(!test_tree.contains(i));
+    }
+    for i in 0..100 {
+        if i % 7 == 0 {
+            assert!(test_map.remove(i).is_some());
         }
+        assert_eq!(test_map.remove(i), None);
     }
+    assert_eq!(test_map, VEBMap::new(100));
 }
 
 #[test]
-fn test_search_correctness(){
-    for i in 0..16 {
-        let mut test_tree = VEBTree::new(16);
-        for j in 0..i {
-            test_tree.insert(j);
+fn test_map_get_mut(){
+    let mut test_map = VEBMap::<u32, u32>::new(64);
+    for i in 0..64 {
+        test_map.insert(i, 0);
+    }
+    for i in 0..64 {
+        *test_map.get_mut(i).unwrap() += i * 2;
+    }
+    for i in 0..64 {
+        assert_eq!(test_map.get(i), Some(&(i * 2)));
+    }
+}
+
+#[test]
+fn test_map_neighbours(){
+    let mut test_map = VEBMap::<usize, char>::new(16);
+    assert_eq!(test_map.minimum(), None);
+    test_map.insert(3, 'a');
+    test_map.insert(9, 'b');
+    test_map.insert(12, 'c');
+    assert_eq!(test_map.minimum(), Some((3, &'a')));
+    assert_eq!(test_map.maximum(), Some((12, &'c')));
+    assert_eq!(test_map.findnext(3), Some((9, &'b')));
+    assert_eq!(test_map.findnext(9), Some((12, &'c')));
+    assert_eq!(test_map.findnext(12), None);
+    assert_eq!(test_map.findprev(12), Some((9, &'b')));
+    assert_eq!(test_map.findprev(9), Some((3, &'a')));
+    assert_eq!(test_map.findprev(3), None);
+    test_map.remove(9);
+    assert_eq!(test_map.findnext(3), Some((12, &'c')));
+}
+
+#[test]
+fn test_sparse_huge_universe(){
+    let mut test_tree = VEBTree::<u64>::new_sparse(usize::MAX);
+    let values = [0, 1, 7, 1 << 20, (1 << 32) + 5, 1 << 40, usize::MAX as u64 - 1];
+    for &value in values.iter().rev() {
+        test_tree.insert(value);
+    }
+    for &value in values.iter() {
+        assert!(test_tree.contains(value));
+        assert!(!test_tree.contains(value ^ 2));
+    }
+    assert_eq!(test_tree.minimum(), Some(0));
+    assert_eq!(test_tree.maximum(), Some(usize::MAX as u64 - 1));
+    for pair in values.windows(2) {
+        assert_eq!(test_tree.findnext(pair[0]), Some(pair[1]));
+        assert_eq!(test_tree.findprev(pair[1]), Some(pair[0]));
+    }
+    for &value in values.iter() {
+        test_tree.delete(value);
+    }
+    assert_eq!(test_tree, VEBTree::new_sparse(usize::MAX));
+}
+
+#[test]
+fn test_sparse_matches_dense(){
+    let mut dense_tree = VEBTree::<u32>::new(1000);
+    let mut sparse_tree = VEBTree::<u32>::new_sparse(1000);
+    let mut value = 1_u32;
+    for step in 0..2000 {
+        value = (value * 577 + 31) % 1000;
+        if step % 3 == 2 {
+            dense_tree.delete(value);
+            sparse_tree.delete(value);
+        } else {
+            dense_tree.insert(value);
+            sparse_tree.insert(value);
         }
-        for j in 0..i {
-            println!("{:?}", test_tree.contains(j));
-            assert_eq!(test_tree.search(j),Some(j));
+        assert_eq!(dense_tree.minimum(), sparse_tree.minimum());
+        assert_eq!(dense_tree.maximum(), sparse_tree.maximum());
+    }
+    for i in 0..1000 {
+        assert_eq!(dense_tree.contains(i), sparse_tree.contains(i));
+        assert_eq!(dense_tree.findnext(i), sparse_tree.findnext(i));
+        assert_eq!(dense_tree.findprev(i), sparse_tree.findprev(i));
+    }
+}
+
+#[test]
+fn test_sparse_map(){
+    let mut test_map = VEBMap::<u32, &str>::new_sparse(1 << 31);
+    test_map.insert(1 << 30, "big");
+    test_map.insert(3, "small");
+    assert_eq!(test_map.get(1 << 30), Some(&"big"));
+    assert_eq!(test_map.findnext(3), Some((1 << 30, &"big")));
+    assert_eq!(test_map.remove(3), Some("small"));
+    assert_eq!(test_map.minimum(), Some((1 << 30, &"big")));
+}
+
+#[test]
+fn test_iter_correctness(){
+    let mut test_tree = VEBTree::<u16>::new(100);
+    assert_eq!(test_tree.iter().next(), None);
+    for i in 0..100 {
+        if i % 7 == 3 {
+            test_tree.insert(i);
         }
     }
+    let expected: Vec<u16> = (0..100).filter(|i| i % 7 == 3).collect();
+    assert_eq!(test_tree.iter().collect::<Vec<u16>>(), expected);
+    assert_eq!(
+        test_tree.iter().rev().collect::<Vec<u16>>(),
+        expected.iter().rev().cloned().collect::<Vec<u16>>()
+    );
+    let mut count = 0;
+    for (value, &reference) in (&test_tree).into_iter().zip(expected.iter()) {
+        assert_eq!(value, reference);
+        count += 1;
+    }
+    assert_eq!(count, expected.len());
+    assert_eq!(test_tree.into_iter().collect::<Vec<u16>>(), expected);
 }
 
 #[test]
-fn test_insert_and_delete_correctness(){
-    let mut test_tree = VEBTree::new(16);
-    let mut reference_tree = VEBTree::new(16);
-    for i in 0..16 {
+fn test_iter_meets_in_the_middle(){
+    let mut test_tree = VEBTree::<u8>::new_sparse(64);
+    for i in 10..15 {
         test_tree.insert(i);
     }
-    for i in 0..16 {
+    let mut iter = test_tree.iter();
+    assert_eq!(iter.next(), Some(10));
+    assert_eq!(iter.next_back(), Some(14));
+    assert_eq!(iter.next(), Some(11));
+    assert_eq!(iter.next_back(), Some(13));
+    assert_eq!(iter.next(), Some(12));
+    assert_eq!(iter.next_back(), None);
+    assert_eq!(iter.next(), None);
+}
+
+#[test]
+fn test_range_matches_btreeset(){
+    let mut test_tree = VEBTree::<u8>::new(40);
+    let mut reference = BTreeSet::new();
+    for i in 0..40 {
+        if i % 5 == 1 || i % 7 == 0 {
+            test_tree.insert(i);
+            reference.insert(i);
+        }
+    }
+    let bounds = |i: u8| vec![Bound::Included(i), Bound::Excluded(i), Bound::Unbounded];
+    for start in 0..45 {
+        for end in start..45 {
+            for &start_bound in bounds(start).iter() {
+                for &end_bound in bounds(end).iter() {
+                    if start == end
+                        && start_bound == Bound::Excluded(start)
+                        && end_bound == Bound::Excluded(end)
+                    {
+                        continue;
+                    }
+                    let expected: Vec<u8> = reference.range((start_bound, end_bound)).cloned().collect();
+                    let forward: Vec<u8> = test_tree.range((start_bound, end_bound)).collect();
+                    let mut backward: Vec<u8> = test_tree.range((start_bound, end_bound)).rev().collect();
+                    backward.reverse();
+                    assert_eq!(forward, expected);
+                    assert_eq!(backward, expected);
+                    assert_eq!(test_tree.range_count((start_bound, end_bound)), expected.len());
+                }
+            }
+        }
+    }
+    assert_eq!(test_tree.range(3..=15).collect::<Vec<u8>>(), vec![6, 7, 11, 14]);
+    assert_eq!(test_tree.range(..7).collect::<Vec<u8>>(), vec![0, 1, 6]);
+    assert_eq!(test_tree.range(35..).collect::<Vec<u8>>(), vec![35, 36]);
+}
+
+#[test]
+#[should_panic]
+fn test_range_backwards_panics(){
+    let test_tree = VEBTree::<u8>::new(16);
+    test_tree.range((Bound::Included(5), Bound::Excluded(3)));
+}
+
+#[test]
+#[should_panic]
+fn test_range_equal_excluded_panics(){
+    let test_tree = VEBTree::<u8>::new(16);
+    test_tree.range((Bound::Excluded(4), Bound::Excluded(4)));
+}
+
+#[test]
+fn test_len_tracks_changes(){
+    let mut test_tree = VEBTree::<u32>::new(64);
+    assert!(test_tree.is_empty());
+    assert_eq!(test_tree.len(), 0);
+    for i in 0..64 {
+        if i % 3 == 0 {
+            assert!(test_tree.insert(i));
+        }
+    }
+    assert_eq!(test_tree.len(), 22);
+    for i in 0..64 {
+        assert_eq!(test_tree.insert(i), i % 3 != 0);
+    }
+    assert_eq!(test_tree.len(), 64);
+    assert_eq!(test_tree.iter().len(), 64);
+    for i in 0..64 {
+        if i % 2 == 0 {
+            assert!(test_tree.delete(i));
+            assert!(!test_tree.delete(i));
+        }
+    }
+    assert!(!test_tree.delete(100));
+    assert_eq!(test_tree.len(), 32);
+    let mut iter = test_tree.iter();
+    iter.next();
+    iter.next_back();
+    assert_eq!(iter.len(), 30);
+    for i in 0..64 {
         test_tree.delete(i);
     }
-    assert_eq!(test_tree, reference_tree);
+    assert!(test_tree.is_empty());
+}
+
+#[test]
+fn test_map_len(){
+    let mut test_map = VEBMap::<u8, u8>::new(16);
+    assert!(test_map.is_empty());
+    test_map.insert(3, 1);
+    test_map.insert(3, 2);
+    test_map.insert(9, 3);
+    assert_eq!(test_map.len(), 2);
+    test_map.remove(4);
+    test_map.remove(3);
+    assert_eq!(test_map.len(), 1);
 }
 
 #[test]
-fn test_insert_and_delete_correctness_odd(){
-    for i in 17..32 {
-        println!("{}", i);
-        let mut test_tree = VEBTree::new(i);
-        let mut reference_tree = VEBTree::new(i);
-        for j in 0..i {
-            test_tree.insert(j);
+fn test_insert_and_remove_match_btreeset(){
+    let mut test_tree = VEBTree::<u16>::new_sparse(1000);
+    let mut reference = BTreeSet::new();
+    let mut value = 7_u16;
+    for step in 0..3000 {
+        value = ((u32::from(value) * 97 + 13) % 1000) as u16;
+        match step % 4 {
+            0 | 1 => assert_eq!(test_tree.insert(value), reference.insert(value)),
+            2 => assert_eq!(test_tree.remove(value), reference.remove(&value)),
+            _ => assert_eq!(test_tree.take(value), reference.take(&value)),
         }
-        for j in 0..i {
-            test_tree.delete(j);
+        assert_eq!(test_tree.len(), reference.len());
+    }
+    assert_eq!(test_tree.iter().collect::<Vec<u16>>(), reference.into_iter().collect::<Vec<u16>>());
+}
+
+#[test]
+fn test_out_of_range_keys(){
+    let mut test_tree = VEBTree::<u8>::new(16);
+    assert_eq!(test_tree.universe(), 16);
+    test_tree.insert(3);
+    let err = VebError::OutOfRange { key: 100, universe: 16 };
+    assert_eq!(test_tree.try_insert(100), Err(err));
+    assert_eq!(test_tree.try_contains(100), Err(err));
+    assert_eq!(test_tree.try_delete(100), Err(err));
+    assert_eq!(test_tree.try_insert(16), Err(VebError::OutOfRange { key: 16, universe: 16 }));
+    assert_eq!(test_tree.try_insert(15), Ok(true));
+    assert_eq!(test_tree.try_insert(15), Ok(false));
+    assert_eq!(test_tree.try_contains(15), Ok(true));
+    assert_eq!(test_tree.try_delete(15), Ok(true));
+    assert_eq!(test_tree.try_delete(15), Ok(false));
+    assert!(!test_tree.contains(100));
+    assert_eq!(test_tree.search(100), None);
+    assert!(!test_tree.delete(100));
+    assert_eq!(test_tree.take(100), None);
+    assert_eq!(test_tree.len(), 1);
+    assert_eq!(test_tree.minimum(), Some(3));
+    assert_eq!(test_tree.maximum(), Some(3));
+    assert_eq!(err.to_string(), "key 100 is outside of the universe of 16");
+}
+
+#[test]
+fn test_out_of_range_keys_odd_universe(){
+    // A universe of 17 is a single u64 bitmap leaf, whose spare bits
+    // 17..64 must still be rejected
+    let mut test_tree = VEBTree::<u32>::new_sparse(17);
+    for i in 17..70 {
+        assert!(test_tree.try_insert(i).is_err());
+    }
+    assert!(test_tree.is_empty());
+    let test_map = VEBMap::<u128, ()>::new(17);
+    assert_eq!(test_map.universe(), 17);
+    assert_eq!(test_map.get(u128::MAX), None);
+}
+
+#[test]
+#[should_panic(expected = "outside of the universe")]
+fn test_insert_out_of_range_panics(){
+    let mut test_tree = VEBTree::<u8>::new(16);
+    test_tree.insert(100);
+}
+
+/// Builds a VEBTree and a BTreeSet holding the same pseudo-random
+///     values.
+fn random_pair(universe: u32, seed: u32, count: usize) -> (VEBTree<u32>, BTreeSet<u32>) {
+    let mut test_tree = VEBTree::new(universe as usize);
+    let mut reference = BTreeSet::new();
+    let mut value = seed;
+    for _ in 0..count {
+        value = (value.wrapping_mul(1_103_515_245).wrapping_add(12_345)) % universe;
+        test_tree.insert(value);
+        reference.insert(value);
+    }
+    (test_tree, reference)
+}
+
+#[test]
+fn test_set_algebra_matches_btreeset(){
+    for &(count_a, count_b) in [(0, 0), (0, 50), (50, 0), (30, 300), (300, 300), (400, 20)].iter() {
+        let (tree_a, set_a) = random_pair(500, 1, count_a);
+        let (tree_b, set_b) = random_pair(500, 2, count_b);
+        assert_eq!(tree_a.union(&tree_b).collect::<Vec<u32>>(), set_a.union(&set_b).cloned().collect::<Vec<u32>>());
+        assert_eq!(tree_a.intersection(&tree_b).collect::<Vec<u32>>(), set_a.intersection(&set_b).cloned().collect::<Vec<u32>>());
+        assert_eq!(tree_a.difference(&tree_b).collect::<Vec<u32>>(), set_a.difference(&set_b).cloned().collect::<Vec<u32>>());
+        assert_eq!(
+            tree_a.symmetric_difference(&tree_b).collect::<Vec<u32>>(),
+            set_a.symmetric_difference(&set_b).cloned().collect::<Vec<u32>>()
+        );
+        assert_eq!(tree_a.is_subset(&tree_b), set_a.is_subset(&set_b));
+        assert_eq!(tree_a.is_superset(&tree_b), set_a.is_superset(&set_b));
+        assert_eq!(tree_a.is_disjoint(&tree_b), set_a.is_disjoint(&set_b));
+
+        let mut union = tree_a.clone();
+        union.union_with(&tree_b);
+        assert_eq!(union.iter().collect::<Vec<u32>>(), set_a.union(&set_b).cloned().collect::<Vec<u32>>());
+        let mut intersection = tree_a.clone();
+        intersection.intersection_with(&tree_b);
+        assert_eq!(intersection.iter().collect::<Vec<u32>>(), set_a.intersection(&set_b).cloned().collect::<Vec<u32>>());
+        let mut difference = tree_a.clone();
+        difference.difference_with(&tree_b);
+        assert_eq!(difference.iter().collect::<Vec<u32>>(), set_a.difference(&set_b).cloned().collect::<Vec<u32>>());
+        let mut symmetric = tree_a.clone();
+        symmetric.symmetric_difference_with(&tree_b);
+        assert_eq!(
+            symmetric.iter().collect::<Vec<u32>>(),
+            set_a.symmetric_difference(&set_b).cloned().collect::<Vec<u32>>()
+        );
+        assert_eq!(symmetric.len(), set_a.symmetric_difference(&set_b).count());
+    }
+}
+
+#[test]
+fn test_subset_relations(){
+    let mut small = VEBTree::<u8>::new(64);
+    let mut large = VEBTree::<u8>::new(64);
+    for i in 0..64 {
+        large.insert(i);
+        if i % 4 == 0 {
+            small.insert(i);
         }
-        assert_eq!(test_tree, reference_tree);
     }
+    assert!(small.is_subset(&large));
+    assert!(large.is_superset(&small));
+    assert!(!large.is_subset(&small));
+    assert!(!small.is_disjoint(&large));
+    large.difference_with(&small);
+    assert!(small.is_disjoint(&large));
+    assert!(VEBTree::<u8>::new(64).is_subset(&small));
 }
+
 #[test]
-fn test_minimum_correctness(){
-    let mut test_tree = VEBTree::new(16);
-    for i in (0..16).rev() {
-        test_tree.insert(i);
-        assert_eq!(test_tree.minimum(), Some(i));
+#[should_panic(expected = "same universe")]
+fn test_set_algebra_needs_same_universe(){
+    let a = VEBTree::<u8>::new(16);
+    let b = VEBTree::<u8>::new(32);
+    a.union(&b);
+}
+
+#[test]
+fn test_rank_and_select_match_btreeset(){
+    for &(universe, count) in [(2, 2), (16, 10), (17, 30), (500, 50), (500, 400), (4096, 1000)].iter() {
+        let (test_tree, reference) = random_pair(universe, 3, count);
+        let sorted: Vec<u32> = reference.iter().cloned().collect();
+        for value in 0..universe + 2 {
+            assert_eq!(test_tree.rank(value), reference.range(..value).count());
+        }
+        for (k, &value) in sorted.iter().enumerate() {
+            assert_eq!(test_tree.select(k), Some(value));
+            assert_eq!(test_tree.rank(value), k);
+        }
+        assert_eq!(test_tree.select(sorted.len()), None);
     }
-    for i in 0..15 {
-        test_tree.delete(i);
-        assert_eq!(test_tree.minimum(), Some(i+1));
+}
+
+#[test]
+fn test_rank_after_deletes(){
+    let mut test_tree = VEBTree::<u16>::new_sparse(1 << 16);
+    for i in 0..1000 {
+        test_tree.insert(i * 37);
     }
-    for i in 0..16 {
-        test_tree.insert(i);
+    for i in 0..1000 {
+        if i % 3 == 0 {
+            test_tree.delete(i * 37);
+        }
     }
-    for i in 0..16 {
-        if i % 2 == 0 {
-            test_tree.delete(i);
-        } else {
-            assert_eq!(test_tree.minimum(), Some(i));
-            test_tree.delete(i);
+    let remaining: Vec<u16> = test_tree.iter().collect();
+    assert_eq!(remaining.len(), test_tree.len());
+    for (k, &value) in remaining.iter().enumerate() {
+        assert_eq!(test_tree.rank(value), k);
+        assert_eq!(test_tree.rank(value + 1), k + 1);
+        assert_eq!(test_tree.select(k), Some(value));
+    }
+    assert_eq!(test_tree.range_count(37..=370), test_tree.range(37..=370).count());
+}
+
+#[test]
+fn test_rank_and_select_across_a_full_width_universe(){
+    let mut test_tree = VEBTree::<u64>::with_max_key_sparse(u64::MAX);
+    let mut reference = BTreeSet::new();
+    let mut key = 1u64;
+    for _ in 0..400 {
+        key = key.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        test_tree.insert(key);
+        reference.insert(key);
+    }
+    for removed in reference.iter().cloned().step_by(3).collect::<Vec<_>>() {
+        test_tree.delete(removed);
+        reference.remove(&removed);
+    }
+    assert_eq!(test_tree.validate(), Ok(()));
+    for (k, &value) in reference.iter().enumerate() {
+        assert_eq!(test_tree.select(k), Some(value));
+        assert_eq!(test_tree.rank(value), k);
+        assert_eq!(test_tree.rank(value.saturating_add(1)), k + 1);
+    }
+    assert_eq!(test_tree.select(reference.len()), None);
+    assert_eq!(test_tree.rank(u64::MAX), reference.range(..u64::MAX).count());
+}
+
+#[test]
+fn test_from_sorted_iter_matches_inserts(){
+    for &universe in [0usize, 1, 2, 3, 5, 16, 17, 100, 1000, 4099].iter() {
+        for &step in [1usize, 2, 7, 31].iter() {
+            let keys: Vec<u32> = (0..universe).step_by(step).map(|key| key as u32).collect();
+            let mut dense = VEBTree::new(universe);
+            let mut sparse = VEBTree::new_sparse(universe);
+            for &key in keys.iter() {
+                dense.insert(key);
+                sparse.insert(key);
+            }
+            // The layout is canonical for a given set of keys, so the
+            // bulk built trees must be identical to the inserted ones
+            assert_eq!(VEBTree::from_sorted_iter(universe, keys.iter().cloned()), dense);
+            assert_eq!(VEBTree::from_sorted_iter_sparse(universe, keys.iter().cloned()), sparse);
         }
     }
 }
 
 #[test]
-fn test_maximum_correctness(){
-    let mut test_tree = VEBTree::new(16);
-    for i in 0..16 {
-        test_tree.insert(i);
-        assert_eq!(test_tree.maximum(), Some(i));
+fn test_from_sorted_iter_unsorted_input(){
+    let (expected, reference) = random_pair(5000, 9, 2000);
+    let mut shuffled: Vec<u32> = Vec::new();
+    for &key in reference.iter().rev() {
+        // Every key twice and in descending order
+        shuffled.push(key);
+        shuffled.push(key);
     }
-    for i in (0..16).rev() {
-        assert_eq!(test_tree.maximum(), Some(i));
-        test_tree.delete(i);
+    let test_tree = VEBTree::from_sorted_iter(5000, shuffled);
+    assert_eq!(test_tree, expected);
+    assert_eq!(test_tree.len(), reference.len());
+    let mut copy = test_tree.clone();
+    assert!(copy.delete(reference.iter().next().cloned().unwrap()));
+    assert_eq!(copy.len(), reference.len() - 1);
+}
+
+#[test]
+#[should_panic(expected = "key 100 is outside of the universe of 100")]
+fn test_from_sorted_iter_out_of_range_panics(){
+    VEBTree::<u16>::from_sorted_iter(100, vec![1, 50, 100]);
+}
+
+#[test]
+fn test_from_iterator_and_extend(){
+    // Collected trees cover the whole key type, sparse, so they can
+    // keep growing like the BTreeSet they stand in for
+    let mut test_tree: VEBTree<u16> = vec![40, 3, 17, 3, 999].into_iter().collect();
+    assert_eq!(test_tree.max_key(), Some(u16::MAX));
+    assert!(test_tree.is_sparse());
+    assert_eq!(test_tree.iter().collect::<Vec<u16>>(), vec![3, 17, 40, 999]);
+    assert!(test_tree.insert(1000));
+    assert!(test_tree.insert(u16::MAX));
+    assert_eq!(test_tree.len(), 6);
+    let mut empty: VEBTree<u8> = Vec::new().into_iter().collect();
+    assert!(empty.is_empty());
+    empty.extend(vec![200, 7]);
+    assert_eq!(empty.iter().collect::<Vec<u8>>(), vec![7, 200]);
+    let mut pair: VEBTree<u32> = vec![3u32, 10].into_iter().collect();
+    assert!(pair.insert(11));
+    assert!(pair.contains(11));
+    let widest: VEBTree<u64> = vec![u64::MAX, 5].into_iter().collect();
+    assert_eq!(widest.iter().collect::<Vec<u64>>(), vec![5, u64::MAX]);
+    assert!(widest.heap_size_bytes() < 1 << 16);
+
+    let mut extended = VEBTree::<u16>::new_sparse(2000);
+    extended.extend(vec![5, 1, 1500]);
+    extended.extend(&[7, 5, 1999]);
+    assert!(extended.is_sparse());
+    assert_eq!(extended.iter().collect::<Vec<u16>>(), vec![1, 5, 7, 1500, 1999]);
+    let mut refilled = extended.clone();
+    for value in extended.iter() {
+        refilled.delete(value);
     }
+    refilled.extend(vec![8, 9]);
+    assert_eq!(refilled.iter().collect::<Vec<u16>>(), vec![8, 9]);
+    assert_eq!(refilled.findprev(9), Some(8));
+}
+
+/// Checks every query of a tree against a BTreeSet at every value of
+///     the universe and a couple past it
+fn check_against(test_tree: &VEBTree<u32>, reference: &BTreeSet<u32>) {
+    assert_eq!(test_tree.len(), reference.len());
+    assert_eq!(test_tree.minimum(), reference.iter().next().cloned());
+    assert_eq!(test_tree.maximum(), reference.iter().next_back().cloned());
+    for value in 0..test_tree.universe() as u32 + 2 {
+        assert_eq!(test_tree.contains(value), reference.contains(&value), "contains {}", value);
+        assert_eq!(test_tree.findnext(value), reference.range(value + 1..).next().cloned(), "findnext {}", value);
+        assert_eq!(test_tree.findprev(value), reference.range(..value).next_back().cloned(), "findprev {}", value);
+        assert_eq!(test_tree.rank(value), reference.range(..value).count(), "rank {}", value);
+    }
+    for (rank, &value) in reference.iter().enumerate() {
+        assert_eq!(test_tree.select(rank), Some(value));
+    }
+    assert_eq!(test_tree.select(reference.len()), None);
 }
 
 #[test]
-fn test_findnext_correctness(){
-    let mut test_tree = VEBTree::new(16);
-    for i in 0..16 {
-        if i % 3 == 0 {
-            test_tree.insert(i);
+fn test_bitmap_leaf_boundaries(){
+    for &universe in [1u32, 2, 63, 64, 65, 127, 128, 129, 4097].iter() {
+        for &sparse in [false, true].iter() {
+            let mut test_tree = if sparse {
+                VEBTree::new_sparse(universe as usize)
+            } else {
+                VEBTree::new(universe as usize)
+            };
+            let mut reference = BTreeSet::new();
+            // Fill both ends of every word as well as every third key
+            for value in (0..universe).filter(|value| value % 64 == 0 || value % 64 == 63 || value % 3 == 0) {
+                test_tree.insert(value);
+                reference.insert(value);
+            }
+            check_against(&test_tree, &reference);
+            for value in (0..universe).filter(|value| value % 2 == 0) {
+                assert_eq!(test_tree.delete(value), reference.remove(&value));
+            }
+            check_against(&test_tree, &reference);
         }
     }
-    for i in 0..13 {
-        if i % 3 == 0 {
-            assert_eq!(test_tree.findnext(i), Some(i+3));
+}
+
+#[test]
+fn test_bitmap_leaf_map_values(){
+    let mut test_map: VEBMap<u8, u32> = VEBMap::new(64);
+    for &key in [40u8, 3, 63, 0, 17, 41].iter() {
+        assert_eq!(test_map.insert(key, u32::from(key) * 10), None);
+    }
+    assert_eq!(test_map.insert(17, 1), Some(170));
+    assert_eq!(test_map.get(17), Some(&1));
+    assert_eq!(test_map.minimum(), Some((0, &0)));
+    assert_eq!(test_map.maximum(), Some((63, &630)));
+    assert_eq!(test_map.findnext(17), Some((40, &400)));
+    assert_eq!(test_map.remove(40), Some(400));
+    assert_eq!(test_map.findnext(17), Some((41, &410)));
+    *test_map.get_mut(63).unwrap() += 1;
+    assert_eq!(test_map.remove(63), Some(631));
+    assert_eq!(test_map.maximum(), Some((41, &410)));
+    assert_eq!(test_map.get(64), None);
+    assert_eq!(test_map.len(), 4);
+}
+
+#[test]
+fn test_clone_is_independent(){
+    let mut original = VEBTree::<u32>::new(1 << 20);
+    for value in (0..1 << 20).step_by(97) {
+        original.insert(value);
+    }
+    let mut copy = original.clone();
+    assert_eq!(copy, original);
+    copy.delete(97);
+    copy.insert(98);
+    assert_ne!(copy, original);
+    assert!(original.contains(97));
+    assert!(!original.contains(98));
+    assert_eq!(copy.findnext(0), Some(98));
+    assert_eq!(original.findnext(0), Some(97));
+}
+
+#[test]
+fn test_sparse_churn_reuses_nodes(){
+    // Freed clusters go back to the arena and are handed out again,
+    // which must not leak old contents into the reused nodes
+    let universe = 1u64 << 32;
+    let mut test_tree = VEBTree::<u64>::new_sparse(universe as usize);
+    let mut reference = BTreeSet::new();
+    let mut value = 7u64;
+    for round in 0..20 {
+        for _ in 0..200 {
+            value = value.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            let key = (value >> 16) % universe;
+            if round % 2 == 0 {
+                assert_eq!(test_tree.insert(key), reference.insert(key));
+            } else if let Some(&existing) = reference.iter().next() {
+                assert_eq!(test_tree.delete(existing), reference.remove(&existing));
+            }
         }
+        assert_eq!(test_tree.iter().collect::<Vec<u64>>(), reference.iter().cloned().collect::<Vec<u64>>());
     }
+    let rebuilt = VEBTree::from_sorted_iter_sparse(universe as usize, reference.iter().cloned());
+    assert_eq!(test_tree, rebuilt);
 }
 
 #[test]
-fn test_findprev_correctness(){
-    let mut test_tree = VEBTree::new(16);
-    for i in 0..16 {
-        if i % 3 == 0 {
-            test_tree.insert(i);
+fn test_every_universe_size(){
+    // Every size up to 10,000 in both layouts, with keys at both ends
+    // of the universe and spread out in between
+    for size in 1..=10_000u32 {
+        let step = (size / 17).max(1);
+        let mut keys: Vec<u32> = (0..size).step_by(step as usize).collect();
+        if keys.last() != Some(&(size - 1)) {
+            keys.push(size - 1);
+        }
+        for &sparse in [false, true].iter() {
+            let mut test_tree = if sparse {
+                VEBTree::new_sparse(size as usize)
+            } else {
+                VEBTree::new(size as usize)
+            };
+            assert_eq!(test_tree.universe(), size as usize);
+            assert_eq!(test_tree.try_insert(size), Err(VebError::OutOfRange { key: size, universe: size }));
+            for &key in keys.iter() {
+                assert!(test_tree.insert(key));
+            }
+            assert_eq!(test_tree.iter().collect::<Vec<u32>>(), keys, "size {}", size);
+            assert_eq!(test_tree.iter().rev().collect::<Vec<u32>>(), keys.iter().rev().cloned().collect::<Vec<u32>>());
+            for (index, &key) in keys.iter().enumerate() {
+                assert_eq!(test_tree.rank(key), index);
+                assert_eq!(test_tree.findnext(key), keys.get(index + 1).cloned());
+                if key > 0 {
+                    assert_eq!(test_tree.findnext(key - 1), Some(key));
+                    assert_eq!(test_tree.findprev(key), index.checked_sub(1).map(|prev| keys[prev]));
+                }
+                assert_eq!(test_tree.findprev(key + 1), Some(key));
+            }
+            for &key in keys.iter() {
+                assert!(test_tree.delete(key));
+            }
+            assert!(test_tree.is_empty());
+            assert_eq!(test_tree.minimum(), None);
         }
     }
-    for i in 3..16 {
-        if i % 3 == 0 {
-            assert_eq!(test_tree.findprev(i), Some(i-3));
+}
+
+#[test]
+fn test_pop_min_and_max(){
+    let (mut test_tree, mut reference) = random_pair(3000, 5, 800);
+    while !reference.is_empty() {
+        assert_eq!(test_tree.pop_min(), reference.pop_first());
+        assert_eq!(test_tree.pop_max(), reference.pop_last());
+        assert_eq!(test_tree.len(), reference.len());
+        assert_eq!(test_tree.minimum(), reference.first().cloned());
+    }
+    assert_eq!(test_tree.pop_min(), None);
+    assert_eq!(test_tree.pop_max(), None);
+
+    // Popping descends on its own rather than through remove, so check
+    // that it leaves the bounds, counts and sparse clusters consistent
+    let mut sparse = VEBTree::<u64>::with_max_key_sparse(u64::MAX);
+    let mut reference = BTreeSet::new();
+    for i in 0..300u64 {
+        let key = i.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (i % 40);
+        sparse.insert(key);
+        reference.insert(key);
+    }
+    while !reference.is_empty() {
+        assert_eq!(sparse.pop_max(), reference.pop_last());
+        assert_eq!(sparse.pop_min(), reference.pop_first());
+        assert_eq!(sparse.validate(), Ok(()));
+        assert_eq!(sparse.maximum(), reference.last().cloned());
+        let middle = reference.len() / 2;
+        assert_eq!(sparse.select(middle), reference.iter().nth(middle).cloned());
+    }
+    assert!(sparse.is_empty());
+
+    let mut test_map: VEBMap<u16, char> = VEBMap::new_sparse(1000);
+    test_map.insert(500, 'b');
+    test_map.insert(7, 'a');
+    test_map.insert(999, 'c');
+    assert_eq!(test_map.pop_max(), Some((999, 'c')));
+    assert_eq!(test_map.pop_min(), Some((7, 'a')));
+    assert_eq!(test_map.pop_min(), Some((500, 'b')));
+    assert_eq!(test_map.pop_min(), None);
+}
+
+#[test]
+fn test_priority_queue_buckets(){
+    let mut queue: VebPriorityQueue<u8, &str> = VebPriorityQueue::new(100);
+    assert_eq!(queue.pop_min(), None);
+    queue.push(5, "first five");
+    queue.push(90, "ninety");
+    queue.push(5, "second five");
+    queue.push(1, "one");
+    queue.push(90, "second ninety");
+    assert_eq!(queue.len(), 5);
+    assert_eq!(queue.count(5), 2);
+    assert_eq!(queue.count(6), 0);
+    assert_eq!(queue.try_push(100, "too big"), Err(VebError::OutOfRange { key: 100, universe: 100 }));
+    assert_eq!(queue.peek_min(), Some((1, &"one")));
+    assert_eq!(queue.peek_max(), Some((90, &"ninety")));
+    assert_eq!(queue.pop_min(), Some((1, "one")));
+    assert_eq!(queue.pop_min(), Some((5, "first five")));
+    assert_eq!(queue.pop_max(), Some((90, "ninety")));
+    assert_eq!(queue.pop_min(), Some((5, "second five")));
+    assert_eq!(queue.pop_min(), Some((90, "second ninety")));
+    assert!(queue.is_empty());
+    assert_eq!(queue.peek_max(), None);
+}
+
+#[test]
+fn test_priority_queue_dijkstra(){
+    // Shortest paths over a grid with integer edge weights, checked
+    // against a plain O(n^2) Dijkstra
+    let side = 12usize;
+    let weight = |from: usize, to: usize| ((from * 31 + to * 17) % 9 + 1) as u32;
+    let neighbours = |node: usize| {
+        let (row, col) = (node / side, node % side);
+        let mut result = Vec::new();
+        if row > 0 { result.push(node - side); }
+        if row + 1 < side { result.push(node + side); }
+        if col > 0 { result.push(node - 1); }
+        if col + 1 < side { result.push(node + 1); }
+        result
+    };
+
+    let mut distances = vec![u32::MAX; side * side];
+    let mut queue: VebPriorityQueue<u32, usize> = VebPriorityQueue::new_sparse(1 << 16);
+    distances[0] = 0;
+    queue.push(0, 0);
+    while let Some((distance, node)) = queue.pop_min() {
+        if distance > distances[node] {
+            continue;
+        }
+        for next in neighbours(node) {
+            let candidate = distance + weight(node, next);
+            if candidate < distances[next] {
+                distances[next] = candidate;
+                queue.push(candidate, next);
+            }
+        }
+    }
+
+    let mut expected = vec![u32::MAX; side * side];
+    let mut done = vec![false; side * side];
+    expected[0] = 0;
+    for _ in 0..side * side {
+        let node = (0..side * side).filter(|&node| !done[node]).min_by_key(|&node| expected[node]).unwrap();
+        done[node] = true;
+        for next in neighbours(node) {
+            expected[next] = expected[next].min(expected[node] + weight(node, next));
+        }
+    }
+    assert_eq!(distances, expected);
+}
+
+#[test]
+fn test_multiset_matches_btreemap(){
+    let mut test_set = VebMultiset::<u16>::new(500);
+    let mut reference: BTreeMap<u16, usize> = BTreeMap::new();
+    let mut value = 11u32;
+    for step in 0..4000 {
+        value = value.wrapping_mul(1_103_515_245).wrapping_add(12_345) % (1 << 31);
+        let key = (value % 500) as u16;
+        match step % 5 {
+            0..=2 => {
+                let count = reference.entry(key).or_insert(0);
+                *count += 1;
+                assert_eq!(test_set.insert(key), *count);
+            }
+            3 => {
+                let present = reference.contains_key(&key);
+                if reference.get(&key) == Some(&1) {
+                    reference.remove(&key);
+                } else if let Some(count) = reference.get_mut(&key) {
+                    *count -= 1;
+                }
+                assert_eq!(test_set.remove_one(key), present);
+            }
+            _ => {
+                assert_eq!(test_set.remove_all(key), reference.remove(&key).unwrap_or(0));
+            }
+        }
+        assert_eq!(test_set.count(key), reference.get(&key).cloned().unwrap_or(0));
+    }
+    assert_eq!(test_set.len(), reference.values().sum::<usize>());
+    assert_eq!(test_set.distinct_len(), reference.len());
+    assert_eq!(test_set.minimum(), reference.keys().next().cloned());
+    assert_eq!(test_set.maximum(), reference.keys().next_back().cloned());
+    for key in 0..502u16 {
+        assert_eq!(test_set.contains(key), reference.contains_key(&key));
+        assert_eq!(test_set.findnext(key), reference.range(key + 1..).next().map(|(&key, _)| key));
+        assert_eq!(test_set.findprev(key), reference.range(..key).next_back().map(|(&key, _)| key));
+    }
+}
+
+#[test]
+fn test_multiset_counts(){
+    let mut test_set = VebMultiset::<u32>::new_sparse(1 << 30);
+    assert!(test_set.is_empty());
+    for _ in 0..3 {
+        test_set.insert(70_000);
+    }
+    test_set.insert(5);
+    assert_eq!(test_set.len(), 4);
+    assert_eq!(test_set.distinct_len(), 2);
+    assert_eq!(test_set.findnext(5), Some(70_000));
+    assert_eq!(test_set.findprev(70_000), Some(5));
+    assert!(test_set.remove_one(70_000));
+    assert_eq!(test_set.count(70_000), 2);
+    assert_eq!(test_set.remove_all(70_000), 2);
+    assert_eq!(test_set.remove_all(70_000), 0);
+    assert!(!test_set.remove_one(70_000));
+    assert_eq!(test_set.len(), 1);
+    assert_eq!(test_set.try_insert(1 << 30), Err(VebError::OutOfRange { key: 1 << 30, universe: 1 << 30 }));
+    assert_eq!(test_set.count(1 << 30), 0);
+}
+
+#[test]
+fn test_ceil_floor_match_btreeset(){
+    for &(universe, count) in [(1u32, 1), (64, 20), (100, 0), (700, 40), (5000, 900)].iter() {
+        let (dense, reference) = random_pair(universe, 13, count);
+        let sparse = VEBTree::from_sorted_iter_sparse(universe as usize, reference.iter().cloned());
+        for test_tree in [dense, sparse].iter() {
+            for value in 0..universe + 3 {
+                let ceil = reference.range(value..).next().cloned();
+                let floor = reference.range(..=value).next_back().cloned();
+                assert_eq!(test_tree.ceil(value), ceil, "ceil {}", value);
+                assert_eq!(test_tree.floor(value), floor, "floor {}", value);
+                assert_eq!(test_tree.lower_bound(value), ceil);
+                assert_eq!(test_tree.upper_bound(value), floor);
+                let nearest = match (floor, ceil) {
+                    (Some(below), Some(above)) if value - below <= above - value => Some(below),
+                    (Some(_), Some(above)) => Some(above),
+                    (below, above) => below.or(above),
+                };
+                assert_eq!(test_tree.nearest(value, TieBreak::Lower), nearest, "nearest {}", value);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_nearest_tie_break(){
+    let test_tree: VEBTree<u8> = vec![10, 20, 200].into_iter().collect();
+    assert_eq!(test_tree.nearest(15, TieBreak::Lower), Some(10));
+    assert_eq!(test_tree.nearest(15, TieBreak::Higher), Some(20));
+    assert_eq!(test_tree.nearest(16, TieBreak::Lower), Some(20));
+    assert_eq!(test_tree.nearest(20, TieBreak::Higher), Some(20));
+    assert_eq!(test_tree.nearest(0, TieBreak::Higher), Some(10));
+    assert_eq!(test_tree.nearest(255, TieBreak::Lower), Some(200));
+    assert_eq!(VEBTree::<u8>::new(10).nearest(5, TieBreak::Lower), None);
+
+    let mut test_map: VEBMap<u16, &str> = VEBMap::new(300);
+    test_map.insert(100, "hundred");
+    test_map.insert(250, "two fifty");
+    assert_eq!(test_map.ceil(100), Some((100, &"hundred")));
+    assert_eq!(test_map.ceil(101), Some((250, &"two fifty")));
+    assert_eq!(test_map.floor(249), Some((100, &"hundred")));
+    assert_eq!(test_map.floor(99), None);
+}
+
+#[test]
+fn test_neighbours_total_over_u8(){
+    // Every u8 is queried against trees of assorted universes and
+    // contents, including every value past the end of the universe
+    for &universe in [0usize, 1, 2, 3, 7, 63, 64, 65, 100, 128, 129, 200, 255, 256].iter() {
+        for seed in 0..12u32 {
+            let mut test_tree = VEBTree::<u8>::new(universe);
+            let mut reference = BTreeSet::new();
+            let mut value = seed;
+            let count = match seed {
+                0 => 0,
+                1 => universe,
+                _ => (seed as usize * 7) % (universe + 1),
+            };
+            for _ in 0..count {
+                value = value.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                let key = if seed == 1 { reference.len() } else { (value >> 8) as usize % universe };
+                test_tree.insert(key as u8);
+                reference.insert(key as u8);
+            }
+            for query in 0..=255u8 {
+                assert_eq!(test_tree.findnext(query), reference.range(query..).find(|&&key| key > query).cloned(), "findnext {} in {}", query, universe);
+                assert_eq!(test_tree.findprev(query), reference.range(..query).next_back().cloned(), "findprev {} in {}", query, universe);
+                assert_eq!(test_tree.ceil(query), reference.range(query..).next().cloned());
+                assert_eq!(test_tree.floor(query), reference.range(..=query).next_back().cloned());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_neighbours_at_the_top_of_the_key_type(){
+    let mut test_tree = VEBTree::<u64>::new_sparse(usize::MAX);
+    let top = usize::MAX as u64 - 1;
+    test_tree.insert(3);
+    test_tree.insert(top);
+    assert_eq!(test_tree.findnext(top), None);
+    assert_eq!(test_tree.findnext(u64::MAX), None);
+    assert_eq!(test_tree.findprev(u64::MAX), Some(top));
+    assert_eq!(test_tree.findnext(3), Some(top));
+    assert_eq!(test_tree.findprev(top), Some(3));
+    assert_eq!(test_tree.ceil(u64::MAX), None);
+    assert_eq!(test_tree.floor(u64::MAX), Some(top));
+
+    let mut wide = VEBTree::<u128>::new_sparse(1 << 40);
+    wide.insert(1 << 39);
+    assert_eq!(wide.findnext(0), Some(1 << 39));
+    assert_eq!(wide.findnext(u128::MAX), None);
+    assert_eq!(wide.findprev(u128::MAX), Some(1 << 39));
+    assert_eq!(wide.findprev(1 << 39), None);
+}
+
+#[test]
+fn test_validate_after_every_operation(){
+    for &universe in [1usize, 64, 65, 300, 5000].iter() {
+        let mut dense: VEBTree<u32> = VEBTree::new(universe);
+        let mut sparse: VEBTree<u32> = VEBTree::new_sparse(universe);
+        let mut test_map: VEBMap<u32, u32> = VEBMap::new_sparse(universe);
+        let mut value = 7u32;
+        for step in 0..600u32 {
+            value = value.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let key = (value >> 8) % universe as u32;
+            if step % 3 == 2 {
+                dense.delete(key);
+                sparse.delete(key);
+                test_map.remove(key);
+            } else {
+                dense.insert(key);
+                sparse.insert(key);
+                test_map.insert(key, step);
+            }
+            assert_eq!(dense.validate(), Ok(()), "dense {} step {}", universe, step);
+            assert_eq!(sparse.validate(), Ok(()), "sparse {} step {}", universe, step);
+            assert_eq!(test_map.validate(), Ok(()), "map {} step {}", universe, step);
+        }
+        while sparse.pop_min().is_some() {
+            assert_eq!(sparse.validate(), Ok(()));
+        }
+        let bulk = VEBTree::from_sorted_iter_sparse(universe, dense.iter());
+        assert_eq!(bulk.validate(), Ok(()));
+    }
+}
+
+// Out of order input to the bulk builder is the one way to put a tree
+// into an inconsistent state from inside the crate
+#[test]
+#[cfg(not(feature = "debug-invariants"))]
+fn test_validate_reports_corruption(){
+    use InvariantViolation;
+
+    let test_tree: VEBTree<u32> = VEBTree::from_sorted_keys(Some(255), false, vec![200, 3]);
+    assert_eq!(test_tree.validate(), Err(InvariantViolation::MinAboveMax { node: 0 }));
+
+    let test_tree: VEBTree<u32> = VEBTree::from_sorted_keys(Some(63), true, vec![3, 3]);
+    assert_eq!(
+        test_tree.validate(),
+        Err(InvariantViolation::CountMismatch { node: 0, expected: 1, found: 2 })
+    );
+
+    // 300 and 290 share a cluster, so 290 is recorded as the maximum
+    // while 300 sits above it
+    let test_tree: VEBTree<u32> = VEBTree::from_sorted_keys(Some(1023), true, vec![5, 300, 290]);
+    assert_eq!(test_tree.validate(), Err(InvariantViolation::MaxNotLargest { node: 0 }));
+}
+
+#[test]
+#[cfg(feature = "debug-invariants")]
+#[should_panic(expected = "Data structure appears corrupt")]
+fn test_debug_invariants_panic_on_corruption(){
+    VEBTree::<u32>::from_sorted_keys(Some(255), false, vec![200, 3]);
+}
+
+#[test]
+fn test_stats_of_dense_and_sparse_trees(){
+    // 2^16 splits into 256 clusters of 256, each of those into four
+    // bitmap leaves
+    let mut dense: VEBTree<u32> = VEBTree::new(1 << 16);
+    let stats = dense.stats();
+    let levels: Vec<(usize, usize, usize)> = stats
+        .levels
+        .iter()
+        .map(|level| (level.universe, level.nodes, level.leaves))
+        .collect();
+    assert_eq!(levels, vec![(1 << 16, 1, 0), (256, 256, 0), (64, 1024, 1024)]);
+    assert_eq!(stats.leaves, 1024);
+    assert_eq!(stats.allocated_clusters, 1280);
+    assert_eq!(stats.populated_clusters, 0);
+    assert_eq!(stats.aux_nodes, 262);
+    assert!(stats.aux_bytes > 0);
+    assert_eq!(stats.heap_size_bytes(), dense.heap_size_bytes());
+
+    // The minimum of a node is never stored in its clusters
+    dense.insert(0);
+    dense.insert(300);
+    assert_eq!(dense.stats().populated_clusters, 1);
+    dense.insert(301);
+    assert_eq!(dense.stats().populated_clusters, 2);
+    assert_eq!(dense.stats().levels[2].populated, 1);
+
+    let mut sparse: VEBTree<u32> = VEBTree::new_sparse(1 << 16);
+    assert_eq!(sparse.stats().levels.len(), 1);
+    assert_eq!(sparse.stats().aux_bytes, 0);
+    for &value in [0, 300, 301].iter() {
+        sparse.insert(value);
+    }
+    let stats = sparse.stats();
+    assert_eq!(stats.levels.iter().map(|level| level.nodes).collect::<Vec<usize>>(), vec![1, 1, 1]);
+    assert_eq!((stats.allocated_clusters, stats.populated_clusters), (2, 2));
+    assert!(sparse.heap_size_bytes() * 10 < dense.heap_size_bytes());
+    sparse.delete(300);
+    sparse.delete(301);
+    let stats = sparse.stats();
+    assert_eq!((stats.levels.len(), stats.allocated_clusters, stats.aux_nodes), (1, 0, 0));
+}
+
+#[test]
+fn test_heap_size_counts_map_values(){
+    let mut test_map: VEBMap<u32, [u64; 4]> = VEBMap::new(1000);
+    let empty = test_map.heap_size_bytes();
+    for key in 0..1000 {
+        test_map.insert(key, [0; 4]);
+    }
+    // Every key but the minimum of the top level sits in a leaf, with
+    // its value next to it
+    assert!(test_map.heap_size_bytes() >= empty + 999 * 32);
+    assert_eq!(test_map.stats().heap_size_bytes(), test_map.heap_size_bytes());
+    assert_eq!(test_map.stats().populated_clusters, 16);
+}
+
+#[test]
+fn test_sparse_heap_shrinks_after_deletes(){
+    let mut test_tree = VEBTree::<u64>::new_sparse(1 << 32);
+    let empty = test_tree.heap_size_bytes();
+    let keys: Vec<u64> = (0..1_000u64)
+        .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32)
+        .collect();
+    for &key in keys.iter() {
+        test_tree.insert(key);
+    }
+    let peak = test_tree.heap_size_bytes();
+    for &key in keys[..900].iter() {
+        test_tree.delete(key);
+    }
+    assert!(test_tree.heap_size_bytes() * 4 < peak);
+    assert_eq!(test_tree.validate(), Ok(()));
+
+    // Every removal path hands its nodes back
+    test_tree.pop_min();
+    test_tree.pop_max();
+    for &key in keys[900..].iter() {
+        test_tree.delete(key);
+    }
+    assert!(test_tree.is_empty());
+    assert_eq!(test_tree.validate(), Ok(()));
+    assert!(test_tree.heap_size_bytes() < empty + 1024);
+
+    for &key in keys.iter().take(100) {
+        test_tree.insert(key);
+    }
+    assert_eq!(test_tree.len(), 100);
+    assert_eq!(test_tree.validate(), Ok(()));
+}
+
+#[test]
+fn test_snapshot_round_trip(){
+    let mut dense = VEBTree::<u32>::new(1000);
+    for i in 0..334 {
+        dense.insert(i * 3);
+    }
+    let mut sparse = VEBTree::<u64>::new_sparse(1 << 40);
+    for i in 0..100u64 {
+        sparse.insert(i * i * 1_000_003);
+    }
+    let empty = VEBTree::<u8>::new(200);
+
+    let mut bytes = Vec::new();
+    dense.write_to(&mut bytes).unwrap();
+    // Most of the universe is set, so the bitmap is smaller
+    assert_eq!(bytes.len(), 27 + 125 + 4);
+    assert_eq!(VEBTree::<u32>::read_from(&bytes[..]).unwrap(), dense);
+
+    bytes.clear();
+    sparse.write_to(&mut bytes).unwrap();
+    let loaded = VEBTree::<u64>::read_from(&bytes[..]).unwrap();
+    assert!(loaded.is_sparse());
+    assert_eq!(loaded, sparse);
+
+    bytes.clear();
+    empty.write_to(&mut bytes).unwrap();
+    assert_eq!(VEBTree::<u8>::read_from(&bytes[..]).unwrap(), empty);
+}
+
+#[test]
+fn test_snapshot_format_is_stable(){
+    // Snapshots are shipped between services, this is the exact
+    // encoding of version 1 and must keep reading back
+    let mut test_tree = VEBTree::<u16>::new(1000);
+    for &value in [3, 4, 200, 999].iter() {
+        test_tree.insert(value);
+    }
+    let mut bytes = Vec::new();
+    test_tree.write_to(&mut bytes).unwrap();
+    let expected: Vec<u8> = vec![
+        b'V', b'E', b'B', b'T', 1, 0, 16, 0,
+        0xE7, 0x07,
+        4, 0, 0, 0, 0, 0, 0, 0,
+        0,
+        6, 0, 0, 0, 0, 0, 0, 0,
+        3, 0, 0xC3, 0x01, 0x9E, 0x06,
+        0xBD, 0xBE, 0x99, 0xC3,
+    ];
+    assert_eq!(bytes, expected);
+    assert_eq!(VEBTree::<u16>::read_from(&bytes[..]).unwrap(), test_tree);
+}
+
+#[test]
+fn test_full_width_universes(){
+    // The universe is kept as a max key, so nothing has to fit in a
+    // usize and every value of the key type can be stored
+    let mut wide = VEBTree::<u64>::with_max_key_sparse(u64::MAX);
+    assert_eq!(wide.max_key(), Some(u64::MAX));
+    for &key in &[0, 1 << 40, u64::MAX - 1, u64::MAX] {
+        assert_eq!(wide.try_insert(key), Ok(true));
+    }
+    assert_eq!(wide.findnext(u64::MAX - 1), Some(u64::MAX));
+    assert_eq!(wide.findprev(u64::MAX), Some(u64::MAX - 1));
+    assert_eq!(wide.rank(u64::MAX), 3);
+    assert_eq!(wide.validate(), Ok(()));
+    assert!(wide.delete(u64::MAX));
+    assert_eq!(wide.maximum(), Some(u64::MAX - 1));
+
+    let mut widest = VEBTree::<u128>::with_max_key_sparse(u128::MAX);
+    for &key in &[3, 1 << 64, 1 << 100, u128::MAX] {
+        widest.insert(key);
+    }
+    assert_eq!(widest.iter().collect::<Vec<u128>>(), vec![3, 1 << 64, 1 << 100, u128::MAX]);
+    assert_eq!(widest.findprev(1 << 100), Some(1 << 64));
+    assert_eq!(widest.validate(), Ok(()));
+
+    // Range checks happen in the key type as well
+    let mut narrow = VEBTree::<u128>::with_max_key_sparse((1 << 70) - 1);
+    assert_eq!(narrow.universe(), usize::MAX);
+    assert_eq!(narrow.try_insert(1 << 70), Err(VebError::OutOfRange { key: 1 << 70, universe: 1 << 70 }));
+    assert!(!narrow.contains(1 << 70));
+    assert_eq!(narrow.try_insert((1 << 70) - 1), Ok(true));
+
+    let mut bytes = Vec::new();
+    widest.write_to(&mut bytes).unwrap();
+    assert_eq!(VEBTree::<u128>::read_from(&bytes[..]).unwrap(), widest);
+    bytes.clear();
+    wide.write_to(&mut bytes).unwrap();
+    assert_eq!(VEBTree::<u64>::read_from(&bytes[..]).unwrap(), wide);
+}
+
+#[test]
+fn test_snapshot_rejects_bad_input(){
+    let (test_tree, _) = random_pair(1000, 4, 50);
+    let mut bytes = Vec::new();
+    test_tree.write_to(&mut bytes).unwrap();
+
+    for cut in 0..bytes.len() {
+        match VEBTree::<u32>::read_from(&bytes[..cut]) {
+            Err(SnapshotError::Truncated) => (),
+            other => panic!("truncated to {} bytes gave {:?}", cut, other),
         }
     }
+    for index in 4..bytes.len() {
+        let mut corrupted = bytes.clone();
+        corrupted[index] ^= 0x10;
+        assert!(VEBTree::<u32>::read_from(&corrupted[..]).is_err());
+    }
+    let mut corrupted = bytes.clone();
+    let last = corrupted.len() - 5;
+    corrupted[last] ^= 0x01;
+    match VEBTree::<u32>::read_from(&corrupted[..]) {
+        Err(SnapshotError::ChecksumMismatch { .. }) => (),
+        other => panic!("flipped payload bit gave {:?}", other),
+    }
+
+    let mut corrupted = bytes.clone();
+    corrupted[0] = b'X';
+    match VEBTree::<u32>::read_from(&corrupted[..]) {
+        Err(SnapshotError::BadMagic) => (),
+        other => panic!("bad magic gave {:?}", other),
+    }
+    let mut corrupted = bytes.clone();
+    corrupted[4] = 2;
+    match VEBTree::<u32>::read_from(&corrupted[..]) {
+        Err(SnapshotError::UnsupportedVersion(2)) => (),
+        other => panic!("future version gave {:?}", other),
+    }
+    match VEBTree::<u64>::read_from(&bytes[..]) {
+        Err(SnapshotError::KeyWidthMismatch { expected: 64, found: 32 }) => (),
+        other => panic!("wrong key width gave {:?}", other),
+    }
 }
 
+#[test]
+fn test_snapshot_caps_dense_universes(){
+    // Dense trees above the limit come back sparse with the same keys
+    let mut oversized = VEBTree::<u32>::new(MAX_LOADED_DENSE_UNIVERSE + 1);
+    oversized.insert(3);
+    oversized.insert(MAX_LOADED_DENSE_UNIVERSE as u32);
+    let mut bytes = Vec::new();
+    oversized.write_to(&mut bytes).unwrap();
+    let loaded = VEBTree::<u32>::read_from(&bytes[..]).unwrap();
+    assert!(loaded.is_sparse());
+    assert_eq!(loaded.universe(), oversized.universe());
+    assert!(loaded.iter().eq(oversized.iter()));
+    // Those at the limit keep their layout
+    let mut at_limit = VEBTree::<u32>::new(MAX_LOADED_DENSE_UNIVERSE);
+    at_limit.insert(5);
+    bytes.clear();
+    at_limit.write_to(&mut bytes).unwrap();
+    assert_eq!(VEBTree::<u32>::read_from(&bytes[..]).unwrap(), at_limit);
+    // Sparse trees load at any universe, up to the whole key type
+    let mut test_tree = VEBTree::<u64>::with_max_key_sparse(u64::MAX);
+    test_tree.insert(1 << 40);
+    bytes.clear();
+    test_tree.write_to(&mut bytes).unwrap();
+    assert_eq!(VEBTree::<u64>::read_from(&bytes[..]).unwrap(), test_tree);
+}
+
+#[cfg(feature = "serde")]
+mod serde {
+    use serde_json;
+    use {MAX_LOADED_DENSE_UNIVERSE, VEBTree};
+
+    #[test]
+    fn test_serde_round_trip_keys(){
+        let mut test_tree = VEBTree::<u16>::new_sparse(1 << 16);
+        for i in 0..50 {
+            test_tree.insert(i * 1000);
+        }
+        let json = serde_json::to_string(&test_tree).unwrap();
+        assert!(json.contains("\"Keys\""));
+        let loaded: VEBTree<u16> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, test_tree);
+        assert!(loaded.is_sparse());
+    }
+
+    #[test]
+    fn test_serde_round_trip_runs(){
+        let mut test_tree = VEBTree::<u32>::new(1000);
+        for i in 100..400 {
+            test_tree.insert(i);
+        }
+        test_tree.insert(999);
+        let json = serde_json::to_string(&test_tree).unwrap();
+        assert_eq!(
+            json,
+            "{\"max_key\":999,\"sparse\":false,\"contents\":{\"Runs\":[[100,399],[999,999]]}}"
+        );
+        let loaded: VEBTree<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, test_tree);
+        assert_eq!(loaded.len(), 301);
+    }
+
+    #[test]
+    fn test_serde_empty(){
+        let test_tree = VEBTree::<u8>::new(16);
+        let json = serde_json::to_string(&test_tree).unwrap();
+        let loaded: VEBTree<u8> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, test_tree);
+    }
+
+    #[test]
+    fn test_serde_rejects_invalid_input(){
+        let out_of_range = "{\"max_key\":15,\"sparse\":false,\"contents\":{\"Keys\":[1,16]}}";
+        let err = serde_json::from_str::<VEBTree<u8>>(out_of_range).unwrap_err();
+        assert!(err.to_string().contains("outside of the universe"));
+        let run_out_of_range = "{\"max_key\":15,\"sparse\":true,\"contents\":{\"Runs\":[[10,20]]}}";
+        assert!(serde_json::from_str::<VEBTree<u8>>(run_out_of_range).is_err());
+        let backwards = "{\"max_key\":15,\"sparse\":true,\"contents\":{\"Runs\":[[5,3]]}}";
+        assert!(serde_json::from_str::<VEBTree<u8>>(backwards).is_err());
+        let too_wide = "{\"max_key\":1000,\"sparse\":true,\"contents\":{\"Keys\":[]}}";
+        assert!(serde_json::from_str::<VEBTree<u8>>(too_wide).is_err());
+    }
+
+    #[test]
+    fn test_serde_checks_sizes_before_allocating(){
+        // A dense universe this large would be allocated in full, so
+        // it is rebuilt sparse with the same keys
+        let huge_dense = "{\"max_key\":18446744073709551615,\"sparse\":false,\"contents\":{\"Keys\":[7]}}";
+        let loaded = serde_json::from_str::<VEBTree<u64>>(huge_dense).unwrap();
+        assert!(loaded.is_sparse());
+        assert_eq!(loaded.max_key(), Some(u64::MAX));
+        assert_eq!(loaded.iter().collect::<Vec<u64>>(), vec![7]);
+        let mut oversized = VEBTree::<u32>::new(MAX_LOADED_DENSE_UNIVERSE + 1);
+        oversized.insert(MAX_LOADED_DENSE_UNIVERSE as u32);
+        let json = serde_json::to_string(&oversized).unwrap();
+        let loaded: VEBTree<u32> = serde_json::from_str(&json).unwrap();
+        assert!(loaded.is_sparse());
+        assert_eq!(loaded.universe(), oversized.universe());
+        assert!(loaded.iter().eq(oversized.iter()));
+        // A run over all of it would be expanded in full as well
+        let huge_run = "{\"max_key\":18446744073709551615,\"sparse\":true,\"contents\":{\"Runs\":[[0,18446744073709551615]]}}";
+        let err = serde_json::from_str::<VEBTree<u64>>(huge_run).unwrap_err();
+        assert!(err.to_string().contains("more than can be held in memory"));
+        let overlapping = "{\"max_key\":15,\"sparse\":true,\"contents\":{\"Runs\":[[1,5],[5,7]]}}";
+        let err = serde_json::from_str::<VEBTree<u8>>(overlapping).unwrap_err();
+        assert!(err.to_string().contains("overlaps"));
+        let unsorted = "{\"max_key\":15,\"sparse\":false,\"contents\":{\"Keys\":[9,2,9,4]}}";
+        let loaded: VEBTree<u8> = serde_json::from_str(unsorted).unwrap();
+        assert_eq!(loaded.iter().collect::<Vec<_>>(), vec![2, 4, 9]);
+    }
+}